@@ -23,8 +23,18 @@ macro_rules! impl_sqrt {
 
 impl_sqrt!(f64, f32);
 
-pub trait Vector<T> {
+pub trait Vector<T>: Sized {
+    const LEN: usize;
+
     fn len(self) -> usize;
+
+    /// Component `index` in field order. Panics when `index >= LEN`.
+    fn get(&self, index: usize) -> &T;
+
+    fn get_mut(&mut self, index: usize) -> &mut T;
+
+    /// Builds a vector from `f(0)`, `f(1)`, ... in field order.
+    fn from_fn(f: impl FnMut(usize) -> T) -> Self;
 }
 
 pub trait VectorDot<T: std::ops::Mul>
@@ -122,11 +122,34 @@ fn generate(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
 
     let others = {
         let type_arg = type_param.as_ref().map(|t| quote! (<#t>));
+        let index: Vec<_> = (0..len).collect();
         quote! {
             impl #type_arg ::fixed_vector::Vector<#item_type> for #identifier #generics {
+                const LEN: usize = #len;
+
                 fn len(self) -> usize {
                     #len
                 }
+
+                fn get(&self, index: usize) -> &#item_type {
+                    match index {
+                        #(#index => &self.#field,)*
+                        _ => panic!("index {} out of range for a vector of length {}", index, #len),
+                    }
+                }
+
+                fn get_mut(&mut self, index: usize) -> &mut #item_type {
+                    match index {
+                        #(#index => &mut self.#field,)*
+                        _ => panic!("index {} out of range for a vector of length {}", index, #len),
+                    }
+                }
+
+                fn from_fn(mut f: impl FnMut(usize) -> #item_type) -> Self {
+                    Self {
+                        #(#field: f(#index)),*
+                    }
+                }
             }
         }
     };
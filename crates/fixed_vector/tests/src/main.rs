@@ -1,4 +1,4 @@
-use fixed_vector::fixed_vector;
+use fixed_vector::{fixed_vector, Vector};
 
 #[derive(Debug, PartialEq)]
 #[fixed_vector(i32; x, y, z)]
@@ -32,6 +32,16 @@ fn test_vector3_div() {
     debug_assert!(Vector3 { x: 2, y: 4, z: 6 } / 2 == Vector3 { x: 1, y: 2, z: 3 });
 }
 
+#[test]
+fn test_vector3_components() {
+    let mut v = Vector3::from_fn(|i| i as i32 * 10);
+    *v.get_mut(2) += 1;
+
+    debug_assert!(v == Vector3 { x: 0, y: 10, z: 21 });
+    debug_assert!(*v.get(1) == 10 && Vector3::LEN == 3);
+    debug_assert!(VectorTuple::from_fn(|i| i as i32).1 == 1);
+}
+
 #[fixed_vector(T; x, y, z)]
 struct GenericVector3<T> {
     x: T,
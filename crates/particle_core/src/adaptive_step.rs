@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::SimVector;
+
+/// Splits every `update` into substeps short enough that no particle travels further than
+/// `max_displacement` in one of them, judged from its current velocity and acceleration. Each
+/// substep is chosen afresh from the state the previous one left, so their lengths can differ
+/// within an update. Choose the limit as a fraction of the shortest length the forces vary over,
+/// such as `D_0`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AdaptiveStep {
+    pub max_displacement: f64,
+    /// Substeps allowed per update. Once they run out the rest of the update is taken at once,
+    /// so a system that blows up anyway still finishes its update.
+    pub max_substeps: u32,
+}
+
+impl AdaptiveStep {
+    pub fn new(max_displacement: f64) -> AdaptiveStep {
+        AdaptiveStep {
+            max_displacement,
+            max_substeps: 64,
+        }
+    }
+
+    /// Length of the next substep out of `remaining`, after `taken` substeps of this update: the
+    /// remaining time split evenly over as many substeps as the current state calls for. The last
+    /// substep is exactly `remaining`.
+    pub fn substep<Props, V: SimVector>(
+        &self,
+        particles: &[Particle<Props, V>],
+        accelerations: &[V],
+        remaining: V::Scalar,
+        taken: u32,
+    ) -> V::Scalar {
+        let d = self.max_displacement;
+        let limit = particles
+            .iter()
+            .zip(accelerations)
+            .map(|(p, a)| {
+                let v = p.velocity.length().to_f64();
+                let a = a.length().to_f64();
+                // v t + a t² / 2 = d の正の解．桁落ちしない形で書く．
+                2.0 * d / (v + (v * v + 2.0 * a * d).sqrt())
+            })
+            .fold(f64::INFINITY, f64::min);
+
+        let left = self.max_substeps.saturating_sub(taken).max(1);
+        let count = (remaining.to_f64() / limit)
+            .ceil()
+            .min(left as f64)
+            .max(1.0);
+        remaining / V::Scalar::from_f64(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector2;
+
+    fn moving(vx: f64) -> Particle<()> {
+        Particle {
+            props: (),
+            mass: 1.0,
+            position: Vector2::default(),
+            velocity: Vector2 { x: vx, y: 0.0 },
+            radius: None,
+            age: 0.0,
+            lifetime: None,
+        }
+    }
+
+    #[test]
+    fn test_substeps_limit_displacement() {
+        let step = AdaptiveStep::new(1.0);
+        let particles = [moving(0.0), moving(10.0), moving(-2.0)];
+        let still = [Vector2::default(); 3];
+
+        assert_eq!(step.substep(&particles, &still, 1.0, 0), 0.1);
+        assert_eq!(step.substep(&particles, &still, 0.05, 0), 0.05);
+        assert_eq!(step.substep(&particles[..1], &still, 1.0, 0), 1.0);
+
+        // 静止した粒子は a t² / 2 = d まで進める．
+        let pushed = [Vector2 { x: 0.0, y: 8.0 }];
+        assert_eq!(step.substep(&particles[..1], &pushed, 1.0, 0), 0.5);
+    }
+
+    #[test]
+    fn test_substeps_are_capped() {
+        let step = AdaptiveStep {
+            max_displacement: 1.0,
+            max_substeps: 4,
+        };
+        let particles = [moving(100.0)];
+        let still = [Vector2::default()];
+
+        assert_eq!(step.substep(&particles, &still, 1.0, 0), 0.25);
+        assert_eq!(step.substep(&particles, &still, 0.25, 3), 0.25);
+        assert_eq!(step.substep(&particles, &still, 0.25, 7), 0.25);
+    }
+}
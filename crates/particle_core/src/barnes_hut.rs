@@ -0,0 +1,335 @@
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Below this many levels a cell is split further; deeper cells keep all their sources, which only
+/// happens for (nearly) coincident positions.
+const MAX_DEPTH: usize = 48;
+
+/// Barnes–Hut tree over point sources, each a position with a signed strength: a quadtree in the
+/// plane and an octree in space. Distant cells are summarised by their total strength placed at
+/// their centre.
+#[derive(Default)]
+pub struct BarnesHutTree<V: SimVector = Vector2<f64>> {
+    nodes: Vec<Node<V>>,
+    /// Source indices, ordered so that every node covers a contiguous range.
+    order: Vec<usize>,
+    positions: Vec<V>,
+    strengths: Vec<V::Scalar>,
+}
+
+struct Node<V: SimVector> {
+    min: V,
+    size: V::Scalar,
+    /// Weighted by the magnitude of the strengths, so that opposite charges do not push it away.
+    center: V,
+    strength: V::Scalar,
+    start: usize,
+    end: usize,
+    /// One per orthant, of which the first `2^LEN` are used.
+    children: [Option<u32>; 8],
+}
+
+impl<V: SimVector> Node<V> {
+    fn contains(&self, p: V) -> bool {
+        (0..V::LEN).all(|i| {
+            let min = *self.min.get(i);
+            min <= *p.get(i) && *p.get(i) <= min + self.size
+        })
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.iter().all(Option::is_none)
+    }
+}
+
+impl<V: SimVector> BarnesHutTree<V> {
+    pub fn rebuild(&mut self, sources: impl Iterator<Item = (V, V::Scalar)>) {
+        assert!(V::LEN <= 3, "the tree has at most three dimensions");
+        self.nodes.clear();
+        self.positions.clear();
+        self.strengths.clear();
+        for (position, strength) in sources {
+            self.positions.push(position);
+            self.strengths.push(strength);
+        }
+        self.order.clear();
+        self.order.extend(0..self.positions.len());
+        let Some(&first) = self.positions.first() else {
+            return;
+        };
+
+        let (min, max) = self
+            .positions
+            .iter()
+            .fold((first, first), |(min, max), &p| {
+                (min.zip_map(p, Scalar::min), max.zip_map(p, Scalar::max))
+            });
+        let extent = max - min;
+        let size = (0..V::LEN)
+            .map(|i| *extent.get(i))
+            .fold(V::Scalar::ZERO, Scalar::max);
+        // 全て同じ位置なら大きさは何でもよい．
+        let size = if size > V::Scalar::ZERO {
+            size
+        } else {
+            V::Scalar::ONE
+        };
+        self.build(0, self.positions.len(), min, size, 0);
+    }
+
+    fn build(&mut self, start: usize, end: usize, min: V, size: V::Scalar, depth: usize) -> u32 {
+        let mut strength = V::Scalar::ZERO;
+        let mut weight = V::Scalar::ZERO;
+        let mut weighted = V::default();
+        for &i in &self.order[start..end] {
+            strength += self.strengths[i];
+            weight += self.strengths[i].abs();
+            weighted += self.positions[i] * self.strengths[i].abs();
+        }
+        let half = size / V::Scalar::from_f64(2.0);
+        let mid = min.map(|x| x + half);
+        let center = if weight > V::Scalar::ZERO {
+            weighted / weight
+        } else {
+            mid
+        };
+
+        let index = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            min,
+            size,
+            center,
+            strength,
+            start,
+            end,
+            children: [None; 8],
+        });
+        if end - start <= 1 || depth >= MAX_DEPTH {
+            return index;
+        }
+
+        // 軸 i で中央以上なら i ビット目を立てる．
+        let orthant = |p: V| {
+            (0..V::LEN)
+                .map(|i| ((p.get(i) >= mid.get(i)) as usize) << i)
+                .sum::<usize>()
+        };
+        let positions = &self.positions;
+        self.order[start..end].sort_by_key(|&i| orthant(positions[i]));
+
+        let mut from = start;
+        for q in 0..1 << V::LEN {
+            let mut to = from;
+            while to < end && orthant(self.positions[self.order[to]]) == q {
+                to += 1;
+            }
+            if to > from {
+                let offset = V::from_fn(|i| V::Scalar::from_f64(((q >> i) & 1) as f64) * half);
+                let child = self.build(from, to, min + offset, half, depth + 1);
+                self.nodes[index as usize].children[q] = Some(child);
+            }
+            from = to;
+        }
+        index
+    }
+
+    /// Calls `f` with the position and strength of sources as seen from `position`, covering every
+    /// source except `exclude` exactly once. A cell that does not contain `position` and whose size
+    /// is less than `theta` times its distance is passed as a single source; `theta = 0` visits
+    /// every source individually.
+    pub fn for_each_source(
+        &self,
+        position: V,
+        theta: f64,
+        exclude: usize,
+        mut f: impl FnMut(V, V::Scalar),
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        self.visit(0, position, V::Scalar::from_f64(theta), exclude, &mut f);
+    }
+
+    // 深さは MAX_DEPTH までなので再帰でよく，粒子ごとにスタックを確保せずに済む．
+    fn visit(
+        &self,
+        n: u32,
+        position: V,
+        theta: V::Scalar,
+        exclude: usize,
+        f: &mut impl FnMut(V, V::Scalar),
+    ) {
+        let node = &self.nodes[n as usize];
+        if node.is_leaf() {
+            for &i in &self.order[node.start..node.end] {
+                if i != exclude {
+                    f(self.positions[i], self.strengths[i]);
+                }
+            }
+        } else if !node.contains(position) && node.size < theta * (node.center - position).length()
+        {
+            f(node.center, node.strength);
+        } else {
+            for &child in node.children.iter().flatten() {
+                self.visit(child, position, theta, exclude, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::particle_system::{Particle, ParticleSystem, ParticleSystemParameters};
+    use crate::rng::SimRng;
+    use crate::vector::Vector3;
+
+    /// Softened Newtonian gravity.
+    struct Gravity;
+
+    impl ParticleSystemParameters for Gravity {
+        type Props = ();
+
+        type Vector = Vector2<f64>;
+
+        fn external_force(&self, _: &Particle<()>, _: f64, _: &mut SimRng) -> Vector2<f64> {
+            Vector2::default()
+        }
+
+        fn source_strength(&self, p: &Particle<()>, _: usize) -> f64 {
+            p.mass
+        }
+
+        fn source_force(
+            &self,
+            p_target: &Particle<()>,
+            position: Vector2<f64>,
+            strength: f64,
+            _: usize,
+        ) -> Vector2<f64> {
+            let delta = position - p_target.position;
+            let r2 = delta.square_length() + 0.01;
+            delta * (p_target.mass * strength / (r2 * r2.sqrt()))
+        }
+    }
+
+    fn random_particles(count: usize) -> Vec<Particle<()>> {
+        let mut rng = SmallRng::seed_from_u64(16);
+        (0..count)
+            .map(|_| Particle {
+                props: (),
+                mass: rng.gen_range(0.5..2.0),
+                position: Vector2 {
+                    x: rng.gen_range(-100.0..100.0),
+                    y: rng.gen_range(-100.0..100.0),
+                },
+                velocity: Vector2::default(),
+                radius: None,
+                age: 0.0,
+                lifetime: None,
+            })
+            .collect()
+    }
+
+    fn direct_sum(particles: &[Particle<()>]) -> Vec<Vector2<f64>> {
+        particles
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let mut a = Vector2::default();
+                for (j, q) in particles.iter().enumerate() {
+                    if i != j {
+                        a += Gravity.source_force(p, q.position, q.mass, 0) / p.mass;
+                    }
+                }
+                a
+            })
+            .collect()
+    }
+
+    /// Root mean square of the acceleration error relative to the root mean square acceleration.
+    fn relative_error(theta: f64, particles: &[Particle<()>], exact: &[Vector2<f64>]) -> f64 {
+        let dt = 1e-3;
+        let mut system = ParticleSystem::new(Gravity, particles.to_vec()).with_barnes_hut(theta);
+        system.update(dt);
+
+        let mut error = 0.0;
+        let mut norm = 0.0;
+        for (p, a) in system.particles().iter().zip(exact) {
+            error += (p.velocity / dt - *a).square_length();
+            norm += a.square_length();
+        }
+        (error / norm).sqrt()
+    }
+
+    #[test]
+    fn test_every_source_is_visited_once() {
+        let particles = random_particles(300);
+        let mut tree = BarnesHutTree::default();
+        tree.rebuild(particles.iter().map(|p| (p.position, p.mass)));
+
+        for theta in [0.0, 0.5, 1.0] {
+            let mut total = 0.0;
+            tree.for_each_source(particles[7].position, theta, 7, |_, s| total += s);
+            let expected: f64 = particles.iter().map(|p| p.mass).sum::<f64>() - particles[7].mass;
+            assert!((total - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_octree_visits_every_source_once() {
+        let mut rng = SmallRng::seed_from_u64(17);
+        let sources: Vec<_> = (0..300)
+            .map(|_| {
+                let position = Vector3 {
+                    x: rng.gen_range(-100.0..100.0),
+                    y: rng.gen_range(-100.0..100.0),
+                    z: rng.gen_range(-100.0..100.0),
+                };
+                (position, rng.gen_range(0.5..2.0))
+            })
+            .collect();
+        let mut tree = BarnesHutTree::default();
+        tree.rebuild(sources.iter().copied());
+
+        for theta in [0.0, 0.5, 1.0] {
+            let mut total = 0.0;
+            tree.for_each_source(sources[7].0, theta, 7, |_, s| total += s);
+            let expected: f64 = sources.iter().map(|&(_, s)| s).sum::<f64>() - sources[7].1;
+            assert!((total - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_matches_direct_sum() {
+        let particles = random_particles(1000);
+        let exact = direct_sum(&particles);
+
+        let errors = [0.0, 0.3, 0.7].map(|theta| relative_error(theta, &particles, &exact));
+
+        assert!(errors[0] < 1e-9);
+        assert!(errors[1] < 1e-3);
+        assert!(errors[2] < 1e-2);
+        assert!(errors[1] < errors[2]);
+    }
+
+    #[test]
+    fn test_coincident_sources() {
+        let mut tree = BarnesHutTree::default();
+        tree.rebuild([(Vector2::default(), 1.0); 5].into_iter());
+
+        let mut sources = Vec::new();
+        tree.for_each_source(Vector2 { x: 10.0, y: 0.0 }, 0.0, usize::MAX, |_, s| {
+            sources.push(s)
+        });
+        assert_eq!(sources, [1.0; 5]);
+
+        sources.clear();
+        tree.for_each_source(Vector2 { x: 10.0, y: 0.0 }, 0.5, usize::MAX, |_, s| {
+            sources.push(s)
+        });
+        assert_eq!(sources, [5.0]);
+    }
+}
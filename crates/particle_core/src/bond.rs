@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::Boundary;
+use crate::particle_id::ParticleId;
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::SimVector;
+
+/// Damped spring between two particles. It is dropped once either particle is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bond {
+    pub a: ParticleId,
+    pub b: ParticleId,
+    pub rest_length: f64,
+    pub stiffness: f64,
+    /// Resists the relative velocity along the bond.
+    pub damping: f64,
+    /// Relative change of length, stretched or compressed, beyond which the bond breaks.
+    pub break_strain: Option<f64>,
+}
+
+impl Bond {
+    /// Force on `a`. `b` feels the opposite one.
+    pub fn force<Props, V: SimVector>(
+        &self,
+        boundary: &Boundary<V>,
+        a: &Particle<Props, V>,
+        b: &Particle<Props, V>,
+    ) -> V {
+        let delta = boundary.separation(a.position, b.position);
+        let length = delta.length();
+        if length < V::Scalar::from_f64(1e-12) {
+            return V::default();
+        }
+        let normal = delta / length;
+        let speed = (b.velocity - a.velocity).dot(normal);
+        let [stiffness, rest_length, damping] =
+            [self.stiffness, self.rest_length, self.damping].map(V::Scalar::from_f64);
+        normal * (stiffness * (length - rest_length) + damping * speed)
+    }
+
+    pub fn strain<Props, V: SimVector>(
+        &self,
+        boundary: &Boundary<V>,
+        a: &Particle<Props, V>,
+        b: &Particle<Props, V>,
+    ) -> f64 {
+        let length = boundary
+            .separation(a.position, b.position)
+            .length()
+            .to_f64();
+        (length - self.rest_length) / self.rest_length
+    }
+
+    pub fn is_broken<Props, V: SimVector>(
+        &self,
+        boundary: &Boundary<V>,
+        a: &Particle<Props, V>,
+        b: &Particle<Props, V>,
+    ) -> bool {
+        self.break_strain
+            .is_some_and(|limit| self.strain(boundary, a, b).abs() > limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use super::*;
+    use crate::integrator::{Integrator, VelocityVerlet};
+    use crate::particle_system::ParticleSystem;
+    use crate::test_util::{scalar_tests, tolerance, Free};
+    use crate::vector::Vector2;
+
+    fn pair<S: Scalar>(particles: [(f64, f64, f64); 2]) -> ParticleSystem<Free<Vector2<S>>> {
+        let particles = particles.map(|(x, vx, mass)| Particle {
+            props: (),
+            mass: S::from_f64(mass),
+            position: Vector2 {
+                x: S::from_f64(x),
+                y: S::ZERO,
+            },
+            velocity: Vector2 {
+                x: S::from_f64(vx),
+                y: S::ZERO,
+            },
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        });
+        ParticleSystem::new(Free(PhantomData), particles)
+    }
+
+    fn bond<S: Scalar>(
+        system: &ParticleSystem<Free<Vector2<S>>, impl Integrator>,
+        break_strain: Option<f64>,
+    ) -> Bond {
+        let ids = system.ids();
+        Bond {
+            a: ids[0],
+            b: ids[1],
+            rest_length: 1.0,
+            stiffness: 4.0,
+            damping: 0.0,
+            break_strain,
+        }
+    }
+
+    fn length<S: Scalar>(system: &ParticleSystem<Free<Vector2<S>>, impl Integrator>) -> f64 {
+        let [a, b] = system.particles() else {
+            unreachable!()
+        };
+        (b.position - a.position).length().to_f64()
+    }
+
+    fn test_spring_oscillates_and_conserves_momentum<S: Scalar>() {
+        let mut system =
+            pair::<S>([(-0.75, 0.0, 1.0), (0.75, 0.3, 3.0)]).with_integrator(VelocityVerlet);
+        system.add_bond(bond(&system, None));
+        let before = system.diagnostics().momentum;
+
+        let mut shortest = f64::INFINITY;
+        let mut longest: f64 = 0.0;
+        for _ in 0..2000 {
+            system.update(S::from_f64(0.005));
+            shortest = shortest.min(length(&system));
+            longest = longest.max(length(&system));
+        }
+
+        let drift = (system.diagnostics().momentum - before).length().to_f64();
+        assert!(drift < tolerance::<S>());
+        assert!(shortest < 1.0 && longest > 1.4);
+    }
+
+    fn test_damping_settles_at_rest_length<S: Scalar>() {
+        let mut system = pair::<S>([(-1.0, 0.0, 1.0), (1.0, 0.0, 1.0)]);
+        system.add_bond(Bond {
+            damping: 2.0,
+            ..bond(&system, None)
+        });
+        for _ in 0..2000 {
+            system.update(S::from_f64(0.01));
+        }
+
+        // f32 では位置の丸めで静止する長さがずれる．
+        let tolerance = tolerance::<S>().max(1e-6);
+        assert!((length(&system) - 1.0).abs() < tolerance);
+    }
+
+    fn test_overstretched_bond_breaks_once<S: Scalar>() {
+        let mut system = pair::<S>([(-0.5, -1.0, 1.0), (0.5, 1.0, 1.0)]);
+        let bond = bond(&system, Some(0.5));
+        system.add_bond(bond);
+        let b = system.ids()[1];
+
+        let mut broken = Vec::new();
+        for _ in 0..100 {
+            system.update(S::from_f64(0.01));
+            broken.extend(system.take_broken_bonds());
+        }
+
+        assert_eq!(broken, [bond]);
+        assert!(system.bonds().is_empty());
+
+        // 消えた粒子への結合は壊れたとは報告せずに取り除く．
+        system.add_bond(bond);
+        system.despawn(b);
+        system.update(S::from_f64(0.01));
+        assert!(system.bonds().is_empty());
+        assert!(system.take_broken_bonds().is_empty());
+    }
+
+    scalar_tests!(
+        test_spring_oscillates_and_conserves_momentum,
+        test_damping_settles_at_rest_length,
+        test_overstretched_bond_breaks_once,
+    );
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Axis aligned box covering `min..max`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Domain<V = Vector2<f64>> {
+    pub min: V,
+    pub max: V,
+}
+
+impl<V: SimVector> Domain<V> {
+    pub fn size(&self) -> V {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, p: V) -> bool {
+        (0..V::LEN).all(|i| self.min.get(i) <= p.get(i) && p.get(i) < self.max.get(i))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Boundary<V = Vector2<f64>> {
+    #[default]
+    Unbounded,
+    /// Toroidal domain. Distances between particles follow the minimum image convention.
+    Periodic(Domain<V>),
+    /// Walls that mirror particles back in. `restitution` of 1 is elastic, smaller values damp the bounce.
+    Reflective { domain: Domain<V>, restitution: f64 },
+    /// Particles leaving the domain are removed.
+    Open(Domain<V>),
+}
+
+impl<V: SimVector> Boundary<V> {
+    pub fn domain(&self) -> Option<&Domain<V>> {
+        match self {
+            Boundary::Unbounded => None,
+            Boundary::Periodic(domain)
+            | Boundary::Reflective { domain, .. }
+            | Boundary::Open(domain) => Some(domain),
+        }
+    }
+
+    pub fn is_periodic(&self) -> bool {
+        matches!(self, Boundary::Periodic(_))
+    }
+
+    /// Vector from `from` to `to`, taking the shortest way around a periodic domain.
+    pub fn separation(&self, from: V, to: V) -> V {
+        let delta = to - from;
+        let Boundary::Periodic(domain) = self else {
+            return delta;
+        };
+        delta.zip_map(domain.size(), |d, l| d - l * (d / l).round())
+    }
+
+    /// Maps a position back into a periodic domain. Other boundaries leave it untouched.
+    pub fn wrap(&self, position: V) -> V {
+        let Boundary::Periodic(domain) = self else {
+            return position;
+        };
+        let wrap = |p: V::Scalar, min: V::Scalar, max: V::Scalar| {
+            let p = min + (p - min).rem_euclid(max - min);
+            // rem_euclid may round up to exactly the period for tiny negative inputs.
+            if p < max {
+                p
+            } else {
+                min
+            }
+        };
+        V::from_fn(|i| wrap(*position.get(i), *domain.min.get(i), *domain.max.get(i)))
+    }
+
+    /// Applies the boundary to a freshly integrated particle.
+    /// Returns `false` when the particle has left an open domain and should be removed.
+    pub fn apply<Props>(&self, p: &mut Particle<Props, V>) -> bool {
+        match self {
+            Boundary::Unbounded => true,
+            Boundary::Periodic(_) => {
+                p.position = self.wrap(p.position);
+                true
+            }
+            Boundary::Reflective {
+                domain,
+                restitution,
+            } => {
+                let restitution = V::Scalar::from_f64(*restitution);
+                let reflect =
+                    |x: &mut V::Scalar, v: &mut V::Scalar, min: V::Scalar, max: V::Scalar| {
+                        if *x < min {
+                            *x = min + (min - *x) * restitution;
+                            *v = v.abs() * restitution;
+                        } else if *x > max {
+                            *x = max - (*x - max) * restitution;
+                            *v = -v.abs() * restitution;
+                        }
+                        *x = x.clamp(min, max);
+                    };
+                for i in 0..V::LEN {
+                    reflect(
+                        p.position.get_mut(i),
+                        p.velocity.get_mut(i),
+                        *domain.min.get(i),
+                        *domain.max.get(i),
+                    );
+                }
+                true
+            }
+            Boundary::Open(domain) => domain.contains(p.position),
+        }
+    }
+}
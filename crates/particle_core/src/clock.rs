@@ -0,0 +1,77 @@
+/// Turns variable frame times into a whole number of fixed simulation steps.
+/// Time that does not fill a step is carried over and exposed through `alpha` for interpolation.
+pub struct SimulationClock {
+    step: f64,
+    max_substeps: u32,
+    accumulator: f64,
+}
+
+impl SimulationClock {
+    pub fn new(step: f64, max_substeps: u32) -> SimulationClock {
+        SimulationClock {
+            step,
+            max_substeps,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Adds `elapsed` seconds and returns how many steps to run now.
+    /// Anything beyond `max_substeps` is dropped, so a long stall does not replay all at once.
+    pub fn advance(&mut self, elapsed: f64) -> u32 {
+        self.accumulator += elapsed.max(0.0);
+        let due = (self.accumulator / self.step).floor();
+        let steps = due.min(self.max_substeps as f64);
+        self.accumulator -= steps * self.step;
+        if due > steps {
+            self.accumulator %= self.step;
+        }
+        steps as u32
+    }
+
+    /// Fraction of a step that has elapsed since the last one, in `[0, 1)`.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_do_not_depend_on_frame_rate() {
+        let mut slow = SimulationClock::new(0.01, 8);
+        let mut fast = SimulationClock::new(0.01, 8);
+
+        let slow_steps: u32 = (0..60).map(|_| slow.advance(1.0 / 60.0)).sum();
+        let fast_steps: u32 = (0..144).map(|_| fast.advance(1.0 / 144.0)).sum();
+
+        let slow_time = (slow_steps as f64 + slow.alpha()) * slow.step();
+        let fast_time = (fast_steps as f64 + fast.alpha()) * fast.step();
+        assert!((slow_time - 1.0).abs() < 1e-9);
+        assert!((fast_time - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stall_is_capped() {
+        let mut clock = SimulationClock::new(0.01, 4);
+
+        assert_eq!(clock.advance(3.005), 4);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(clock.advance(0.0), 0);
+    }
+
+    #[test]
+    fn test_alpha_tracks_remainder() {
+        let mut clock = SimulationClock::new(0.01, 4);
+
+        assert_eq!(clock.advance(0.025), 2);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(clock.advance(0.005), 1);
+        assert!(clock.alpha() < 1e-6);
+    }
+}
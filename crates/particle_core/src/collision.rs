@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::Boundary;
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::spatial_grid::SpatialGrid;
+use crate::vector::SimVector;
+
+/// Hard-sphere contact between particles that have a radius.
+/// Particles without a radius pass through everything.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Collisions {
+    /// 1 keeps the normal speed on bounce, 0 makes colliding particles stick together along the normal.
+    pub restitution: f64,
+    /// Coulomb friction coefficient limiting the tangential impulse.
+    pub friction: f64,
+}
+
+impl Collisions {
+    /// Separates overlapping particles and exchanges momentum between those approaching each other.
+    pub fn resolve<Props, V: SimVector>(
+        &self,
+        particles: &mut [Particle<Props, V>],
+        boundary: &Boundary<V>,
+        grid: &mut SpatialGrid<V>,
+    ) {
+        let max_radius = particles
+            .iter()
+            .filter_map(|p| p.radius)
+            .fold(V::Scalar::ZERO, Scalar::max);
+        if max_radius <= V::Scalar::ZERO {
+            return;
+        }
+
+        let periodic = boundary.domain().filter(|_| boundary.is_periodic());
+        grid.rebuild(
+            particles.iter().map(|p| p.position),
+            max_radius + max_radius,
+            periodic,
+        );
+
+        for i in 0..particles.len() {
+            if particles[i].radius.is_none() {
+                continue;
+            }
+            grid.for_each_neighbor(particles[i].position, |j| {
+                if j > i {
+                    let (head, tail) = particles.split_at_mut(j);
+                    self.resolve_pair(&mut head[i], &mut tail[0], boundary);
+                }
+            });
+        }
+    }
+
+    fn resolve_pair<Props, V: SimVector>(
+        &self,
+        a: &mut Particle<Props, V>,
+        b: &mut Particle<Props, V>,
+        boundary: &Boundary<V>,
+    ) {
+        let (Some(ra), Some(rb)) = (a.radius, b.radius) else {
+            return;
+        };
+        let delta = boundary.separation(a.position, b.position);
+        let distance = delta.length();
+        let overlap = ra + rb - distance;
+        if overlap <= V::Scalar::ZERO {
+            return;
+        }
+
+        let epsilon = V::Scalar::from_f64(1e-12);
+        let normal = if distance > epsilon {
+            delta / distance
+        } else {
+            V::from_fn(|i| {
+                if i == 0 {
+                    V::Scalar::ONE
+                } else {
+                    V::Scalar::ZERO
+                }
+            })
+        };
+        let (wa, wb) = (V::Scalar::ONE / a.mass, V::Scalar::ONE / b.mass);
+        let w = wa + wb;
+
+        a.position -= normal * (overlap * wa / w);
+        b.position += normal * (overlap * wb / w);
+
+        let relative = b.velocity - a.velocity;
+        let vn = relative.dot(normal);
+        if vn >= V::Scalar::ZERO {
+            return;
+        }
+        let jn = -(V::Scalar::ONE + V::Scalar::from_f64(self.restitution)) * vn / w;
+        let mut impulse = normal * jn;
+
+        let sliding = relative - normal * vn;
+        let speed = sliding.length();
+        if speed > epsilon {
+            let tangent = sliding / speed;
+            let jt = (speed / w).min(V::Scalar::from_f64(self.friction) * jn);
+            impulse -= tangent * jt;
+        }
+
+        a.velocity -= impulse * wa;
+        b.velocity += impulse * wb;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::test_util::{scalar_tests, tolerance};
+    use crate::vector::Vector2;
+
+    fn ball<S: Scalar>(x: f64, vx: f64, mass: f64) -> Particle<(), Vector2<S>> {
+        Particle {
+            props: (),
+            mass: S::from_f64(mass),
+            position: Vector2 {
+                x: S::from_f64(x),
+                y: S::ZERO,
+            },
+            velocity: Vector2 {
+                x: S::from_f64(vx),
+                y: S::ZERO,
+            },
+            radius: Some(S::ONE),
+            age: S::ZERO,
+            lifetime: None,
+        }
+    }
+
+    fn resolve<S: Scalar>(collisions: Collisions, particles: &mut [Particle<(), Vector2<S>>]) {
+        collisions.resolve(particles, &Boundary::Unbounded, &mut SpatialGrid::default());
+    }
+
+    fn test_elastic_head_on_collision_exchanges_velocities<S: Scalar>() {
+        let mut balls = [ball::<S>(-0.9, 1.0, 1.0), ball(0.9, -1.0, 1.0)];
+        resolve(
+            Collisions {
+                restitution: 1.0,
+                friction: 0.0,
+            },
+            &mut balls,
+        );
+
+        assert_eq!(balls[0].velocity.x, -S::ONE);
+        assert_eq!(balls[1].velocity.x, S::ONE);
+        let distance = (balls[1].position - balls[0].position).length().to_f64();
+        assert!(distance >= 2.0 - tolerance::<S>());
+    }
+
+    fn test_inelastic_collision_conserves_momentum<S: Scalar>() {
+        let mut balls = [ball::<S>(-0.9, 3.0, 2.0), ball(0.9, 0.0, 1.0)];
+        resolve(
+            Collisions {
+                restitution: 0.0,
+                friction: 0.0,
+            },
+            &mut balls,
+        );
+
+        let tolerance = tolerance::<S>();
+        assert!((balls[0].velocity.x.to_f64() - 2.0).abs() < tolerance);
+        assert!((balls[1].velocity.x.to_f64() - 2.0).abs() < tolerance);
+    }
+
+    fn test_friction_conserves_momentum_and_separates<S: Scalar>() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut draw = |range| S::from_f64(rng.gen_range(range));
+        let mut balls: Vec<_> = (0..200)
+            .map(|_| Particle {
+                props: (),
+                mass: draw(0.5..2.0),
+                position: Vector2 {
+                    x: draw(-10.0..10.0),
+                    y: draw(-10.0..10.0),
+                },
+                velocity: Vector2 {
+                    x: draw(-1.0..1.0),
+                    y: draw(-1.0..1.0),
+                },
+                radius: Some(S::from_f64(0.5)),
+                age: S::ZERO,
+                lifetime: None,
+            })
+            .collect();
+        let momentum = |balls: &[Particle<(), Vector2<S>>]| {
+            balls.iter().fold([0.0; 2], |[x, y], p| {
+                let m = p.mass.to_f64();
+                [x + m * p.velocity.x.to_f64(), y + m * p.velocity.y.to_f64()]
+            })
+        };
+        let overlap = |balls: &[Particle<(), Vector2<S>>]| {
+            let mut total = 0.0;
+            for (i, a) in balls.iter().enumerate() {
+                for b in &balls[i + 1..] {
+                    total += (1.0 - (b.position - a.position).length().to_f64()).max(0.0);
+                }
+            }
+            total
+        };
+
+        let before = (momentum(&balls), overlap(&balls));
+        let collisions = Collisions {
+            restitution: 0.5,
+            friction: 0.3,
+        };
+        for _ in 0..10 {
+            resolve(collisions, &mut balls);
+        }
+
+        let after = momentum(&balls);
+        let drift = (after[0] - before.0[0]).hypot(after[1] - before.0[1]);
+        assert!(drift < tolerance::<S>());
+        assert!(overlap(&balls) < before.1 * 0.1);
+    }
+
+    scalar_tests!(
+        test_elastic_head_on_collision_exchanges_velocities,
+        test_inelastic_collision_conserves_momentum,
+        test_friction_conserves_momentum_and_separates,
+    );
+}
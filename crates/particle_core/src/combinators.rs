@@ -0,0 +1,629 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle_system::{MaybeSync, Particle, ParticleSystemParameters, ScalarOf};
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+
+/// Both parameters acting together. Their forces and potentials add up, and pairs interact up to
+/// the larger cutoff. Under `with_barnes_hut` the fields of the second follow those of the first,
+/// each summed over its own tree.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Sum<A, B>(pub A, pub B);
+
+/// Every force and potential of `params` multiplied by `factor`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Scaled<P> {
+    pub params: P,
+    pub factor: f64,
+}
+
+/// `params` acting only on particles whose props pass `filter`. Pairs interact when both pass.
+pub struct Restricted<P, F> {
+    pub params: P,
+    pub filter: F,
+}
+
+/// `first` until `after` time units have been simulated, then `second`. Nest them to switch
+/// several times; every `after` counts from the start.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Switch<A, B> {
+    pub first: A,
+    pub second: B,
+    pub after: f64,
+    pub elapsed: f64,
+}
+
+impl<A, B> Switch<A, B> {
+    pub fn new(first: A, second: B, after: f64) -> Switch<A, B> {
+        Switch {
+            first,
+            second,
+            after,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl<P: ParticleSystemParameters> Scaled<P> {
+    fn factor(&self) -> ScalarOf<P> {
+        ScalarOf::<P>::from_f64(self.factor)
+    }
+}
+
+impl<A, B> ParticleSystemParameters for Sum<A, B>
+where
+    A: ParticleSystemParameters,
+    B: ParticleSystemParameters<Props = A::Props, Vector = A::Vector>,
+{
+    type Props = A::Props;
+    type Vector = A::Vector;
+
+    fn external_force(
+        &self,
+        p: &Particle<A::Props, A::Vector>,
+        delta_time: ScalarOf<A>,
+        rng: &mut SimRng,
+    ) -> A::Vector {
+        self.0.external_force(p, delta_time, rng) + self.1.external_force(p, delta_time, rng)
+    }
+
+    fn internal_force(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        p_other: &Particle<A::Props, A::Vector>,
+        delta_time: ScalarOf<A>,
+    ) -> ScalarOf<A> {
+        self.0.internal_force(p_target, p_other, delta_time)
+            + self.1.internal_force(p_target, p_other, delta_time)
+    }
+
+    fn internal_force_vector(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        p_other: &Particle<A::Props, A::Vector>,
+        delta_time: ScalarOf<A>,
+    ) -> A::Vector {
+        self.0.internal_force_vector(p_target, p_other, delta_time)
+            + self.1.internal_force_vector(p_target, p_other, delta_time)
+    }
+
+    fn cutoff_radius(&self) -> Option<ScalarOf<A>> {
+        Some(self.0.cutoff_radius()?.max(self.1.cutoff_radius()?))
+    }
+
+    fn symmetric(&self) -> bool {
+        self.0.symmetric() && self.1.symmetric()
+    }
+
+    fn source_fields(&self) -> usize {
+        self.0.source_fields() + self.1.source_fields()
+    }
+
+    fn source_strength(&self, p: &Particle<A::Props, A::Vector>, field: usize) -> ScalarOf<A> {
+        // 後の演算子の場は前の演算子の場の後に続く．
+        let first = self.0.source_fields();
+        if field < first {
+            self.0.source_strength(p, field)
+        } else {
+            self.1.source_strength(p, field - first)
+        }
+    }
+
+    fn source_force(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        position: A::Vector,
+        strength: ScalarOf<A>,
+        field: usize,
+    ) -> A::Vector {
+        let first = self.0.source_fields();
+        if field < first {
+            self.0.source_force(p_target, position, strength, field)
+        } else {
+            self.1
+                .source_force(p_target, position, strength, field - first)
+        }
+    }
+
+    fn pair_potential(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        p_other: &Particle<A::Props, A::Vector>,
+    ) -> Option<ScalarOf<A>> {
+        Some(self.0.pair_potential(p_target, p_other)? + self.1.pair_potential(p_target, p_other)?)
+    }
+
+    fn advance(&mut self, delta_time: ScalarOf<A>) {
+        self.0.advance(delta_time);
+        self.1.advance(delta_time);
+    }
+}
+
+impl<P: ParticleSystemParameters> ParticleSystemParameters for Scaled<P> {
+    type Props = P::Props;
+    type Vector = P::Vector;
+
+    fn external_force(
+        &self,
+        p: &Particle<P::Props, P::Vector>,
+        delta_time: ScalarOf<P>,
+        rng: &mut SimRng,
+    ) -> P::Vector {
+        self.params.external_force(p, delta_time, rng) * self.factor()
+    }
+
+    fn internal_force(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+        delta_time: ScalarOf<P>,
+    ) -> ScalarOf<P> {
+        self.params.internal_force(p_target, p_other, delta_time) * self.factor()
+    }
+
+    fn internal_force_vector(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+        delta_time: ScalarOf<P>,
+    ) -> P::Vector {
+        self.params
+            .internal_force_vector(p_target, p_other, delta_time)
+            * self.factor()
+    }
+
+    fn cutoff_radius(&self) -> Option<ScalarOf<P>> {
+        self.params.cutoff_radius()
+    }
+
+    fn symmetric(&self) -> bool {
+        self.params.symmetric()
+    }
+
+    fn source_fields(&self) -> usize {
+        self.params.source_fields()
+    }
+
+    fn source_strength(&self, p: &Particle<P::Props, P::Vector>, field: usize) -> ScalarOf<P> {
+        self.params.source_strength(p, field)
+    }
+
+    fn source_force(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        position: P::Vector,
+        strength: ScalarOf<P>,
+        field: usize,
+    ) -> P::Vector {
+        self.params
+            .source_force(p_target, position, strength, field)
+            * self.factor()
+    }
+
+    fn pair_potential(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+    ) -> Option<ScalarOf<P>> {
+        self.params
+            .pair_potential(p_target, p_other)
+            .map(|u| u * self.factor())
+    }
+
+    fn advance(&mut self, delta_time: ScalarOf<P>) {
+        self.params.advance(delta_time);
+    }
+}
+
+impl<P, F> ParticleSystemParameters for Restricted<P, F>
+where
+    P: ParticleSystemParameters,
+    F: Fn(&P::Props) -> bool + MaybeSync,
+{
+    type Props = P::Props;
+    type Vector = P::Vector;
+
+    fn external_force(
+        &self,
+        p: &Particle<P::Props, P::Vector>,
+        delta_time: ScalarOf<P>,
+        rng: &mut SimRng,
+    ) -> P::Vector {
+        if (self.filter)(&p.props) {
+            self.params.external_force(p, delta_time, rng)
+        } else {
+            P::Vector::default()
+        }
+    }
+
+    fn internal_force(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+        delta_time: ScalarOf<P>,
+    ) -> ScalarOf<P> {
+        if self.both(p_target, p_other) {
+            self.params.internal_force(p_target, p_other, delta_time)
+        } else {
+            ScalarOf::<P>::ZERO
+        }
+    }
+
+    fn internal_force_vector(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+        delta_time: ScalarOf<P>,
+    ) -> P::Vector {
+        if self.both(p_target, p_other) {
+            self.params
+                .internal_force_vector(p_target, p_other, delta_time)
+        } else {
+            P::Vector::default()
+        }
+    }
+
+    fn cutoff_radius(&self) -> Option<ScalarOf<P>> {
+        self.params.cutoff_radius()
+    }
+
+    fn symmetric(&self) -> bool {
+        self.params.symmetric()
+    }
+
+    fn source_fields(&self) -> usize {
+        self.params.source_fields()
+    }
+
+    fn source_strength(&self, p: &Particle<P::Props, P::Vector>, field: usize) -> ScalarOf<P> {
+        if (self.filter)(&p.props) {
+            self.params.source_strength(p, field)
+        } else {
+            ScalarOf::<P>::ZERO
+        }
+    }
+
+    fn source_force(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        position: P::Vector,
+        strength: ScalarOf<P>,
+        field: usize,
+    ) -> P::Vector {
+        if (self.filter)(&p_target.props) {
+            self.params
+                .source_force(p_target, position, strength, field)
+        } else {
+            P::Vector::default()
+        }
+    }
+
+    fn pair_potential(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+    ) -> Option<ScalarOf<P>> {
+        if self.both(p_target, p_other) {
+            self.params.pair_potential(p_target, p_other)
+        } else {
+            Some(ScalarOf::<P>::ZERO)
+        }
+    }
+
+    fn advance(&mut self, delta_time: ScalarOf<P>) {
+        self.params.advance(delta_time);
+    }
+}
+
+impl<P: ParticleSystemParameters, F: Fn(&P::Props) -> bool> Restricted<P, F> {
+    fn both(
+        &self,
+        p_target: &Particle<P::Props, P::Vector>,
+        p_other: &Particle<P::Props, P::Vector>,
+    ) -> bool {
+        (self.filter)(&p_target.props) && (self.filter)(&p_other.props)
+    }
+}
+
+/// Forwards every method to whichever of `first` and `second` is active.
+macro_rules! forward {
+    ($self:ident, $params:ident => $call:expr) => {
+        if $self.elapsed < $self.after {
+            let $params = &$self.first;
+            $call
+        } else {
+            let $params = &$self.second;
+            $call
+        }
+    };
+}
+
+impl<A, B> ParticleSystemParameters for Switch<A, B>
+where
+    A: ParticleSystemParameters,
+    B: ParticleSystemParameters<Props = A::Props, Vector = A::Vector>,
+{
+    type Props = A::Props;
+    type Vector = A::Vector;
+
+    fn external_force(
+        &self,
+        p: &Particle<A::Props, A::Vector>,
+        delta_time: ScalarOf<A>,
+        rng: &mut SimRng,
+    ) -> A::Vector {
+        forward!(self, params => params.external_force(p, delta_time, rng))
+    }
+
+    fn internal_force(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        p_other: &Particle<A::Props, A::Vector>,
+        delta_time: ScalarOf<A>,
+    ) -> ScalarOf<A> {
+        forward!(self, params => params.internal_force(p_target, p_other, delta_time))
+    }
+
+    fn internal_force_vector(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        p_other: &Particle<A::Props, A::Vector>,
+        delta_time: ScalarOf<A>,
+    ) -> A::Vector {
+        forward!(self, params => params.internal_force_vector(p_target, p_other, delta_time))
+    }
+
+    fn cutoff_radius(&self) -> Option<ScalarOf<A>> {
+        forward!(self, params => params.cutoff_radius())
+    }
+
+    fn symmetric(&self) -> bool {
+        forward!(self, params => params.symmetric())
+    }
+
+    fn source_fields(&self) -> usize {
+        forward!(self, params => params.source_fields())
+    }
+
+    fn source_strength(&self, p: &Particle<A::Props, A::Vector>, field: usize) -> ScalarOf<A> {
+        forward!(self, params => params.source_strength(p, field))
+    }
+
+    fn source_force(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        position: A::Vector,
+        strength: ScalarOf<A>,
+        field: usize,
+    ) -> A::Vector {
+        forward!(self, params => params.source_force(p_target, position, strength, field))
+    }
+
+    fn pair_potential(
+        &self,
+        p_target: &Particle<A::Props, A::Vector>,
+        p_other: &Particle<A::Props, A::Vector>,
+    ) -> Option<ScalarOf<A>> {
+        forward!(self, params => params.pair_potential(p_target, p_other))
+    }
+
+    fn advance(&mut self, delta_time: ScalarOf<A>) {
+        self.elapsed += delta_time.to_f64();
+        self.first.advance(delta_time);
+        self.second.advance(delta_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use super::*;
+    use crate::integrator::VelocityVerlet;
+    use crate::particle_system::ParticleSystem;
+    use crate::test_util::{scalar_tests, tolerance};
+    use crate::vector::{SimVector, Vector2};
+
+    /// Spring between every pair, with the kind as props.
+    #[derive(Clone, Copy)]
+    struct Spring<S>(PhantomData<S>);
+
+    fn spring<S>() -> Spring<S> {
+        Spring(PhantomData)
+    }
+
+    impl<S: Scalar> ParticleSystemParameters for Spring<S> {
+        type Props = usize;
+
+        type Vector = Vector2<S>;
+
+        fn external_force(
+            &self,
+            _: &Particle<usize, Vector2<S>>,
+            _: S,
+            _: &mut SimRng,
+        ) -> Vector2<S> {
+            Vector2::default()
+        }
+
+        fn internal_force(
+            &self,
+            p_target: &Particle<usize, Vector2<S>>,
+            p_other: &Particle<usize, Vector2<S>>,
+            _: S,
+        ) -> S {
+            (p_other.position - p_target.position).length() - S::ONE
+        }
+
+        fn symmetric(&self) -> bool {
+            true
+        }
+
+        /// Under `with_barnes_hut`, a spring towards every source as strong as its kind plus one.
+        fn source_strength(&self, p: &Particle<usize, Vector2<S>>, _: usize) -> S {
+            S::from_f64(1.0 + p.props as f64)
+        }
+
+        fn source_force(
+            &self,
+            p_target: &Particle<usize, Vector2<S>>,
+            position: Vector2<S>,
+            strength: S,
+            _: usize,
+        ) -> Vector2<S> {
+            (position - p_target.position) * strength
+        }
+
+        fn pair_potential(
+            &self,
+            p_target: &Particle<usize, Vector2<S>>,
+            p_other: &Particle<usize, Vector2<S>>,
+        ) -> Option<S> {
+            let stretch = (p_other.position - p_target.position).length() - S::ONE;
+            Some(S::from_f64(0.5) * stretch * stretch)
+        }
+    }
+
+    fn particles<S: Scalar>() -> Vec<Particle<usize, Vector2<S>>> {
+        [(0, 0.0, 0.0), (0, 2.0, 0.0), (1, 0.0, 3.0)]
+            .into_iter()
+            .map(|(kind, x, y)| Particle {
+                props: kind,
+                mass: S::ONE,
+                position: Vector2 {
+                    x: S::from_f64(x),
+                    y: S::from_f64(y),
+                },
+                velocity: Vector2::default(),
+                radius: None,
+                age: S::ZERO,
+                lifetime: None,
+            })
+            .collect()
+    }
+
+    fn run<S: Scalar, P: ParticleSystemParameters<Props = usize, Vector = Vector2<S>>>(
+        params: P,
+        steps: usize,
+    ) -> ParticleSystem<P, VelocityVerlet> {
+        let mut system = ParticleSystem::new(params, particles()).with_integrator(VelocityVerlet);
+        for _ in 0..steps {
+            system.update(S::from_f64(0.01));
+        }
+        system
+    }
+
+    fn run_far<S: Scalar, P: ParticleSystemParameters<Props = usize, Vector = Vector2<S>>>(
+        params: P,
+    ) -> ParticleSystem<P, VelocityVerlet> {
+        let mut system = ParticleSystem::new(params, particles())
+            .with_barnes_hut(0.0)
+            .with_integrator(VelocityVerlet);
+        for _ in 0..200 {
+            system.update(S::from_f64(0.01));
+        }
+        system
+    }
+
+    fn max_difference<S, P, Q>(
+        a: &ParticleSystem<P, VelocityVerlet>,
+        b: &ParticleSystem<Q, VelocityVerlet>,
+    ) -> f64
+    where
+        S: Scalar,
+        P: ParticleSystemParameters<Props = usize, Vector = Vector2<S>>,
+        Q: ParticleSystemParameters<Props = usize, Vector = Vector2<S>>,
+    {
+        a.particles()
+            .iter()
+            .zip(b.particles())
+            .map(|(p, q)| (p.position - q.position).length().to_f64())
+            .fold(0.0, f64::max)
+    }
+
+    fn test_sum_of_parts_matches_scaled_whole<S: Scalar>() {
+        let tolerance = tolerance::<S>();
+        let whole = run(
+            Scaled {
+                params: spring::<S>(),
+                factor: 3.0,
+            },
+            200,
+        );
+        let parts = run(
+            Sum(
+                spring::<S>(),
+                Scaled {
+                    params: spring(),
+                    factor: 2.0,
+                },
+            ),
+            200,
+        );
+
+        assert!(max_difference(&whole, &parts) < tolerance);
+        let (u0, u1) = (
+            whole.potential_energy().unwrap(),
+            parts.potential_energy().unwrap(),
+        );
+        assert!((u0 - u1).abs() < tolerance);
+    }
+
+    fn test_sum_keeps_the_sources_of_both<S: Scalar>() {
+        let whole = run_far(Scaled {
+            params: spring::<S>(),
+            factor: 3.0,
+        });
+        let parts = run_far(Sum(
+            spring::<S>(),
+            Scaled {
+                params: spring(),
+                factor: 2.0,
+            },
+        ));
+
+        assert_eq!(parts.params().source_fields(), 2);
+        assert!(max_difference(&whole, &parts) < tolerance::<S>());
+        assert!(max_difference(&whole, &run_far(spring::<S>())) > 1e-3);
+    }
+
+    fn test_restricted_leaves_other_kinds_alone<S: Scalar>() {
+        let system = run(
+            Restricted {
+                params: spring::<S>(),
+                filter: |kind: &usize| *kind == 0,
+            },
+            200,
+        );
+        let [a, b, c] = system.particles() else {
+            unreachable!()
+        };
+
+        assert_eq!((c.position.x, c.position.y), (S::ZERO, S::from_f64(3.0)));
+        assert!(((b.position - a.position).length().to_f64() - 2.0).abs() > 1e-3);
+    }
+
+    fn test_switch_changes_parameters_after_a_time<S: Scalar>() {
+        // 最初の 100 ステップは何も起きず，その後はばねが働く．
+        let still = Scaled {
+            params: spring::<S>(),
+            factor: 0.0,
+        };
+        let params = Switch::new(still, spring(), 0.995);
+        let idle = run(params, 100);
+        let started = run(params, 200);
+        let delayed = run(spring(), 100);
+
+        assert!(max_difference(&idle, &run(still, 0)) < tolerance::<S>());
+        let tolerance = 1e3 * tolerance::<S>();
+        assert!((idle.params().elapsed - 1.0).abs() < tolerance);
+        assert!(max_difference(&started, &delayed) < tolerance);
+    }
+
+    scalar_tests!(
+        test_sum_of_parts_matches_scaled_whole,
+        test_sum_keeps_the_sources_of_both,
+        test_restricted_leaves_other_kinds_alone,
+        test_switch_changes_parameters_after_a_time,
+    );
+}
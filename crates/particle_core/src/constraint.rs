@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::{Boundary, Domain};
+use crate::particle_id::ParticleId;
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Condition on particle positions enforced by `ConstraintSolver`. It is dropped once any of its
+/// particles is gone.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Constraint<V = Vector2<f64>> {
+    /// Keeps the two particles exactly `length` apart.
+    Distance {
+        a: ParticleId,
+        b: ParticleId,
+        length: f64,
+    },
+    /// Keeps the two particles at least `length` apart.
+    MinDistance {
+        a: ParticleId,
+        b: ParticleId,
+        length: f64,
+    },
+    /// Holds the particle at `position`, as if it had infinite mass.
+    Pin { particle: ParticleId, position: V },
+    /// Keeps the particle inside `region`.
+    Contain {
+        particle: ParticleId,
+        region: Domain<V>,
+    },
+}
+
+impl<V: SimVector> Constraint<V> {
+    /// The particles this constraint acts on.
+    pub fn ids(&self) -> impl Iterator<Item = ParticleId> {
+        let (a, b) = match *self {
+            Constraint::Distance { a, b, .. } | Constraint::MinDistance { a, b, .. } => {
+                (a, Some(b))
+            }
+            Constraint::Pin { particle, .. } | Constraint::Contain { particle, .. } => {
+                (particle, None)
+            }
+        };
+        std::iter::once(a).chain(b)
+    }
+}
+
+/// Position based projection of constraints at the end of each update (XPBD). Positions are
+/// corrected directly and velocities follow the corrections, so stiff structures stay stable at
+/// large time steps.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConstraintSolver {
+    /// Passes over all constraints per update. More passes make chains and meshes stiffer.
+    pub iterations: u32,
+    /// Inverse stiffness of every constraint. 0 is rigid.
+    pub compliance: f64,
+}
+
+impl Default for ConstraintSolver {
+    fn default() -> ConstraintSolver {
+        ConstraintSolver {
+            iterations: 4,
+            compliance: 0.0,
+        }
+    }
+}
+
+/// One scalar constraint `C = 0` (or `C >= 0`) on up to two particles: its value and the gradient
+/// with respect to the position of each particle.
+struct Projection<V: SimVector> {
+    value: V::Scalar,
+    gradients: [(usize, V); 2],
+    count: usize,
+    /// For inequalities, the sign the accumulated multiplier keeps so that they only ever push.
+    sign: Option<V::Scalar>,
+}
+
+impl ConstraintSolver {
+    /// Projects the positions of `particles` onto `constraints`, whose particles are given by
+    /// index, and adds the corrections divided by `delta_time` to the velocities.
+    pub fn solve<Props, V: SimVector>(
+        &self,
+        constraints: &[(Constraint<V>, [usize; 2])],
+        particles: &mut [Particle<Props, V>],
+        boundary: &Boundary<V>,
+        delta_time: V::Scalar,
+    ) {
+        if constraints.is_empty() {
+            return;
+        }
+        let start: Vec<_> = particles.iter().map(|p| p.position).collect();
+        let mut inverse_masses: Vec<_> =
+            particles.iter().map(|p| V::Scalar::ONE / p.mass).collect();
+        for &(constraint, [a, _]) in constraints {
+            if let Constraint::Pin { position, .. } = constraint {
+                particles[a].position = position;
+                inverse_masses[a] = V::Scalar::ZERO;
+            }
+        }
+        let alpha = V::Scalar::from_f64(self.compliance) / (delta_time * delta_time);
+        let mut lambdas = vec![V::Scalar::ZERO; constraints.len()];
+
+        for _ in 0..self.iterations {
+            for (&(constraint, [a, b]), lambda) in constraints.iter().zip(&mut lambdas) {
+                let Some(projection) = Self::project(constraint, a, b, particles, boundary) else {
+                    continue;
+                };
+                let gradients = &projection.gradients[..projection.count];
+                let weight: V::Scalar = gradients
+                    .iter()
+                    .map(|&(i, g)| g.square_length() * inverse_masses[i])
+                    .sum();
+                if weight + alpha <= V::Scalar::ZERO {
+                    continue;
+                }
+                let mut delta_lambda = (-projection.value - alpha * *lambda) / (weight + alpha);
+                if let Some(sign) = projection.sign {
+                    // 不等式の乗数が符号を変えると，柔らかい拘束が粒子を引き戻してしまう．
+                    delta_lambda =
+                        (sign * (*lambda + delta_lambda)).max(V::Scalar::ZERO) * sign - *lambda;
+                }
+                *lambda += delta_lambda;
+                for &(i, g) in gradients {
+                    particles[i].position += g * (delta_lambda * inverse_masses[i]);
+                }
+            }
+        }
+
+        for (p, start) in particles.iter_mut().zip(start) {
+            p.velocity += (p.position - start) / delta_time;
+        }
+    }
+
+    /// `None` when the constraint has no usable gradient, as for a particle inside its region, or
+    /// is a pin, which `solve` enforces directly.
+    fn project<Props, V: SimVector>(
+        constraint: Constraint<V>,
+        a: usize,
+        b: usize,
+        particles: &[Particle<Props, V>],
+        boundary: &Boundary<V>,
+    ) -> Option<Projection<V>> {
+        let pair = |length: f64| {
+            let delta = boundary.separation(particles[a].position, particles[b].position);
+            let distance = delta.length();
+            (distance > V::Scalar::from_f64(1e-12)).then(|| {
+                let normal = delta / distance;
+                Projection {
+                    value: distance - V::Scalar::from_f64(length),
+                    gradients: [(a, -normal), (b, normal)],
+                    count: 2,
+                    sign: None,
+                }
+            })
+        };
+        match constraint {
+            Constraint::Distance { length, .. } => pair(length),
+            // 満たされていても評価し，溜まった乗数は符号の制限の中で戻す．
+            Constraint::MinDistance { length, .. } => pair(length).map(|p| Projection {
+                sign: Some(V::Scalar::ONE),
+                ..p
+            }),
+            Constraint::Pin { .. } => None,
+            Constraint::Contain { region, .. } => {
+                // 領域内の最も近い点までの距離を C とする．
+                let p = particles[a].position;
+                let nearest = p
+                    .zip_map(region.min, Scalar::max)
+                    .zip_map(region.max, Scalar::min);
+                let delta = p - nearest;
+                let distance = delta.length();
+                (distance > V::Scalar::ZERO).then(|| Projection {
+                    value: distance,
+                    gradients: [(a, delta / distance), (a, V::default())],
+                    count: 1,
+                    sign: Some(-V::Scalar::ONE),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use super::*;
+    use crate::particle_system::ParticleSystem;
+    use crate::test_util::{scalar_tests, tolerance, Free};
+
+    fn v<S: Scalar>(x: f64, y: f64) -> Vector2<S> {
+        Vector2 {
+            x: S::from_f64(x),
+            y: S::from_f64(y),
+        }
+    }
+
+    fn particle<S: Scalar>(x: f64, y: f64, vx: f64, vy: f64) -> Particle<(), Vector2<S>> {
+        Particle {
+            props: (),
+            mass: S::ONE,
+            position: v(x, y),
+            velocity: v(vx, vy),
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        }
+    }
+
+    fn pendulum<S: Scalar>(compliance: f64) -> ParticleSystem<Free<Vector2<S>>> {
+        let mut system = ParticleSystem::new(
+            Free(PhantomData),
+            [particle(0.0, 0.0, 0.0, 0.0), particle(1.0, 0.0, 0.0, 2.0)],
+        )
+        .with_constraint_solver(ConstraintSolver {
+            iterations: 4,
+            compliance,
+        });
+        let [pivot, bob] = [system.ids()[0], system.ids()[1]];
+        system.add_constraint(Constraint::Pin {
+            particle: pivot,
+            position: Vector2::default(),
+        });
+        system.add_constraint(Constraint::Distance {
+            a: pivot,
+            b: bob,
+            length: 1.0,
+        });
+        system
+    }
+
+    fn test_pinned_pendulum_keeps_its_length<S: Scalar>() {
+        let tolerance = tolerance::<S>();
+        let mut rigid = pendulum::<S>(0.0);
+        let mut soft = pendulum::<S>(1e-2);
+        let mut soft_error: f64 = 0.0;
+        for _ in 0..500 {
+            rigid.update(S::from_f64(0.05));
+            soft.update(S::from_f64(0.05));
+            let [pivot, bob] = rigid.particles() else {
+                unreachable!()
+            };
+            assert!(pivot.position.length().to_f64() < tolerance);
+            assert!((bob.position.length().to_f64() - 1.0).abs() < tolerance);
+            let length = soft.particles()[1].position.length().to_f64();
+            soft_error = soft_error.max((length - 1.0).abs());
+        }
+
+        // 速度は拘束に沿って円運動の向きに保たれる．
+        let [_, bob] = rigid.particles() else {
+            unreachable!()
+        };
+        assert!(bob.velocity.length().to_f64() > 0.5);
+        assert!(soft_error > 1e-3);
+    }
+
+    fn test_min_distance_and_containment<S: Scalar>() {
+        let mut system = ParticleSystem::new(
+            Free(PhantomData),
+            [
+                particle::<S>(-2.0, 0.0, 3.0, 0.0),
+                particle(2.0, 0.0, -3.0, 0.0),
+            ],
+        );
+        let [a, b] = [system.ids()[0], system.ids()[1]];
+        let region = Domain {
+            min: v(-3.0, -3.0),
+            max: v(3.0, 3.0),
+        };
+        system.add_constraint(Constraint::MinDistance { a, b, length: 1.0 });
+        system.add_constraint(Constraint::Contain {
+            particle: a,
+            region,
+        });
+
+        let limit = S::from_f64(3.0);
+        for _ in 0..200 {
+            system.update(S::from_f64(0.05));
+            let [pa, pb] = system.particles() else {
+                unreachable!()
+            };
+            let distance = (pb.position - pa.position).length().to_f64();
+            assert!(distance > 1.0 - tolerance::<S>());
+            let Vector2 { x, y } = pa.position;
+            assert!((-limit..=limit).contains(&x) && (-limit..=limit).contains(&y));
+        }
+    }
+
+    fn test_compliant_min_distance_only_pushes<S: Scalar>() {
+        // 解く対は添字で渡すので，ID は何でもよい．
+        let system = ParticleSystem::new(
+            Free::<Vector2<S>>(PhantomData),
+            [particle(0.0, 0.0, 0.0, 0.0)],
+        );
+        let id = system.ids()[0];
+        let constraint = Constraint::MinDistance {
+            a: id,
+            b: id,
+            length: 1.0,
+        };
+        let solve = |x: f64, iterations| {
+            let mut particles = [
+                particle::<S>(0.0, 0.0, 0.0, 0.0),
+                particle(x, 0.0, 0.0, 0.0),
+            ];
+            ConstraintSolver {
+                iterations,
+                compliance: 1e-2,
+            }
+            .solve(
+                &[(constraint, [0, 1])],
+                &mut particles,
+                &Boundary::Unbounded,
+                S::from_f64(0.05),
+            );
+            (particles[1].position - particles[0].position).length()
+        };
+
+        // 柔らかくても離れた対は引き寄せない．
+        assert_eq!(solve(2.0, 8), S::from_f64(2.0));
+        // 重なった対は押し広げられ，コンプライアンスの力 α λ と C が釣り合う 2/3 で止まる．
+        for iterations in 1..8 {
+            let distance = solve(0.5, iterations).to_f64();
+            assert!((distance - 2.0 / 3.0).abs() < tolerance::<S>());
+        }
+    }
+
+    scalar_tests!(
+        test_pinned_pendulum_keeps_its_length,
+        test_compliant_min_distance_only_pushes,
+        test_min_distance_and_containment,
+    );
+}
@@ -0,0 +1,191 @@
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Aggregate physical quantities of a set of particles. Boltzmann's constant is taken as 1.
+/// Sums are taken in `f64` whatever the scalar of the particles.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Diagnostics<V: SimVector = Vector2<f64>> {
+    pub count: usize,
+    pub mass: f64,
+    pub kinetic_energy: f64,
+    /// Present when the parameters define `pair_potential`. Each pair's energy is split evenly between its two particles.
+    pub potential_energy: Option<f64>,
+    pub momentum: V,
+    /// Around the centre of mass: a single component in the plane, the full vector in space.
+    pub angular_momentum: V::Cross,
+    pub center_of_mass: V,
+    /// From the kinetic energy relative to the centre of mass motion, one degree of freedom per
+    /// particle and axis.
+    pub temperature: f64,
+}
+
+impl<V: SimVector> Diagnostics<V> {
+    pub fn measure<Props>(
+        particles: &[&Particle<Props, V>],
+        potential_energy: Option<f64>,
+    ) -> Diagnostics<V> {
+        let count = particles.len();
+        let mut mass = 0.0;
+        let mut kinetic_energy = 0.0;
+        let mut momentum = vec![0.0; V::LEN];
+        let mut weighted_position = vec![0.0; V::LEN];
+        for p in particles {
+            let m = p.mass.to_f64();
+            mass += m;
+            kinetic_energy += 0.5 * m * p.velocity.square_length().to_f64();
+            for i in 0..V::LEN {
+                momentum[i] += m * p.velocity.get(i).to_f64();
+                weighted_position[i] += m * p.position.get(i).to_f64();
+            }
+        }
+        if count == 0 || mass == 0.0 {
+            return Diagnostics {
+                potential_energy,
+                ..Diagnostics::default()
+            };
+        }
+
+        let to_vector = |sum: &[f64]| V::from_fn(|i| V::Scalar::from_f64(sum[i] / mass));
+        let center_of_mass = to_vector(&weighted_position);
+        let center_velocity = to_vector(&momentum);
+        let momentum = V::from_fn(|i| V::Scalar::from_f64(momentum[i]));
+        let mut angular_momentum = V::Cross::default();
+        let mut thermal_energy = 0.0;
+        for p in particles {
+            let r = p.position - center_of_mass;
+            let u = p.velocity - center_velocity;
+            angular_momentum += r.cross(u) * p.mass.to_f64();
+            thermal_energy += 0.5 * (p.mass * u.square_length()).to_f64();
+        }
+        let degrees_of_freedom = V::LEN * (count - 1);
+        let temperature = if degrees_of_freedom > 0 {
+            2.0 * thermal_energy / degrees_of_freedom as f64
+        } else {
+            0.0
+        };
+
+        Diagnostics {
+            count,
+            mass,
+            kinetic_energy,
+            potential_energy,
+            momentum,
+            angular_momentum,
+            center_of_mass,
+            temperature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scalar_tests;
+    use crate::vector::Vector3;
+
+    fn particle<S: Scalar>(
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+        mass: f64,
+    ) -> Particle<(), Vector2<S>> {
+        let [x, y, vx, vy, mass] = [x, y, vx, vy, mass].map(S::from_f64);
+        Particle {
+            props: (),
+            mass,
+            position: Vector2 { x, y },
+            velocity: Vector2 { x: vx, y: vy },
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        }
+    }
+
+    fn test_rotating_pair<S: Scalar>() {
+        let a = particle::<S>(-1.0, 0.0, 0.0, -1.0, 1.0);
+        let b = particle(1.0, 0.0, 0.0, 1.0, 1.0);
+        let d = Diagnostics::measure(&[&a, &b], None);
+
+        assert_eq!(d.count, 2);
+        assert_eq!(d.kinetic_energy, 1.0);
+        assert_eq!(d.momentum.length(), S::ZERO);
+        assert_eq!(d.center_of_mass.length(), S::ZERO);
+        assert_eq!(d.angular_momentum, 2.0);
+        assert_eq!(d.temperature, 1.0);
+    }
+
+    fn test_uniform_motion_has_no_temperature<S: Scalar>() {
+        let a = particle::<S>(0.0, 0.0, 3.0, 1.0, 1.0);
+        let b = particle(4.0, 2.0, 3.0, 1.0, 3.0);
+        let d = Diagnostics::measure(&[&a, &b], Some(-2.0));
+
+        let [m0, m1, c0, c1] = [12.0, 4.0, 3.0, 1.5].map(S::from_f64);
+        assert_eq!(d.mass, 4.0);
+        assert_eq!(d.kinetic_energy, 20.0);
+        assert_eq!(d.potential_energy, Some(-2.0));
+        assert_eq!((d.momentum.x, d.momentum.y), (m0, m1));
+        assert_eq!((d.center_of_mass.x, d.center_of_mass.y), (c0, c1));
+        assert_eq!(d.angular_momentum, 0.0);
+        assert_eq!(d.temperature, 0.0);
+    }
+
+    fn test_angular_momentum_in_space<S: Scalar>() {
+        let v = |x, y, z| {
+            let [x, y, z] = [x, y, z].map(S::from_f64);
+            Vector3 { x, y, z }
+        };
+        let particle = |y, z, vy, vz| Particle {
+            props: (),
+            mass: S::from_f64(2.0),
+            position: v(5.0, y, z),
+            velocity: v(1.0, vy, vz),
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        };
+        // 重心に対して yz 平面内で回る対なので，角運動量は x 軸を向く．
+        let a = particle(-1.0, 0.0, 0.0, -1.0);
+        let b = particle(1.0, 0.0, 0.0, 1.0);
+        let d = Diagnostics::measure(&[&a, &b], None);
+
+        let Vector3 { x, y, z } = d.angular_momentum;
+        assert_eq!((x, y, z), (4.0, 0.0, 0.0));
+        assert_eq!(d.temperature, 4.0 / 3.0);
+    }
+
+    #[test]
+    fn test_single_precision_sums_in_double() {
+        let particles: Vec<Particle<(), Vector2<f32>>> = (0..100_000)
+            .map(|i| Particle {
+                props: (),
+                mass: 1.0,
+                position: Vector2 {
+                    x: 1000.0 + (i % 10) as f32 * 0.1,
+                    y: 0.0,
+                },
+                velocity: Vector2 { x: 0.1, y: 0.0 },
+                radius: None,
+                age: 0.0,
+                lifetime: None,
+            })
+            .collect();
+        let all: Vec<_> = particles.iter().collect();
+        let d = Diagnostics::measure(&all, None);
+
+        // f32 で足すと和が 1e8 に達して 1 の位まで丸められる．
+        let center: f64 = (0..10)
+            .map(|i| (1000.0 + i as f32 * 0.1) as f64)
+            .sum::<f64>()
+            / 10.0;
+        assert!((d.center_of_mass.x as f64 - center).abs() < 1e-3);
+        assert!((d.momentum.x as f64 - 1e5 * 0.1f32 as f64).abs() < 1e-2);
+    }
+
+    scalar_tests!(
+        test_rotating_pair,
+        test_uniform_motion_has_no_temperature,
+        test_angular_momentum_in_space,
+    );
+}
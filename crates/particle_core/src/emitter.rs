@@ -0,0 +1,302 @@
+use std::f64::consts::PI;
+use std::ops::Range;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::integrator::Integrator;
+use crate::particle_id::ParticleId;
+use crate::particle_system::{Particle, ParticleSystem, ParticleSystemParameters, ScalarOf};
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Where around the emitter new particles appear, uniformly distributed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SpawnShape<V = Vector2<f64>> {
+    Point,
+    /// Anywhere inside the disc, or the ball in space.
+    Ball {
+        radius: f64,
+    },
+    /// On the circle, or the sphere in space.
+    Sphere {
+        radius: f64,
+    },
+    /// Anywhere inside the rectangle, or the box in space, centred on the emitter.
+    Box {
+        half_size: V,
+    },
+}
+
+/// Spawns copies of `template` at `rate` particles per time unit, scattered over `shape` and
+/// moving away at a speed drawn from `speed`. Directions lie within `spread` radians of
+/// `direction`, uniformly over the cone; a spread of π emits in every direction. The emitter owns
+/// its random numbers, so a seed reproduces the emitted particles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Props: Serialize",
+    deserialize = "Props: Deserialize<'de>"
+))]
+pub struct Emitter<Props, V: SimVector = Vector2<f64>> {
+    pub template: Particle<Props, V>,
+    pub rate: V::Scalar,
+    pub position: V,
+    /// Added to the velocity of every emitted particle, such as the velocity of a moving ship.
+    pub velocity: V,
+    pub shape: SpawnShape<V>,
+    /// Need not be normalised.
+    pub direction: V,
+    pub spread: f64,
+    pub speed: Range<V::Scalar>,
+    /// Fraction of a particle carried over to the next `emit`.
+    pending: V::Scalar,
+    rng: SimRng,
+}
+
+impl<Props: Clone, V: SimVector> Emitter<Props, V> {
+    /// Resting particles at the template position, emitted in every direction.
+    pub fn new(template: Particle<Props, V>, rate: V::Scalar) -> Emitter<Props, V> {
+        Emitter {
+            position: template.position,
+            template,
+            rate,
+            velocity: V::default(),
+            shape: SpawnShape::Point,
+            direction: V::from_fn(|i| {
+                if i == 0 {
+                    V::Scalar::ONE
+                } else {
+                    V::Scalar::ZERO
+                }
+            }),
+            spread: PI,
+            speed: V::Scalar::ZERO..V::Scalar::ZERO,
+            pending: V::Scalar::ZERO,
+            rng: SimRng::new(0),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SimRng::new(seed);
+        self
+    }
+
+    pub fn with_shape(mut self, shape: SpawnShape<V>) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_velocity(mut self, direction: V, spread: f64, speed: Range<V::Scalar>) -> Self {
+        self.direction = direction;
+        self.spread = spread;
+        self.speed = speed;
+        self
+    }
+
+    /// Spawns the particles due over `delta_time` into `system`. Call it once per update.
+    pub fn emit<P, I>(
+        &mut self,
+        system: &mut ParticleSystem<P, I>,
+        delta_time: ScalarOf<P>,
+    ) -> Vec<ParticleId>
+    where
+        P: ParticleSystemParameters<Props = Props, Vector = V>,
+        I: Integrator,
+    {
+        self.pending += self.rate * delta_time;
+        let count = self.pending.floor();
+        self.pending -= count;
+        self.burst(system, count.to_f64() as usize)
+    }
+
+    /// Spawns `count` particles at once, as for an explosion.
+    pub fn burst<P, I>(
+        &mut self,
+        system: &mut ParticleSystem<P, I>,
+        count: usize,
+    ) -> Vec<ParticleId>
+    where
+        P: ParticleSystemParameters<Props = Props, Vector = V>,
+        I: Integrator,
+    {
+        (0..count)
+            .map(|_| {
+                let particle = self.particle();
+                system.spawn(particle)
+            })
+            .collect()
+    }
+
+    fn particle(&mut self) -> Particle<Props, V> {
+        let rng = &mut self.rng;
+        let scalar = V::Scalar::from_f64;
+        let offset = match self.shape {
+            SpawnShape::Point => V::default(),
+            // 体積で一様になるように半径は次元の冪根を取る．
+            SpawnShape::Ball { radius } => {
+                let r = radius * rng.gen::<f64>().powf(1.0 / V::LEN as f64);
+                unit::<V>(rng) * scalar(r)
+            }
+            SpawnShape::Sphere { radius } => unit::<V>(rng) * scalar(radius),
+            SpawnShape::Box { half_size } => {
+                half_size.map(|h| h * scalar(2.0 * rng.gen::<f64>() - 1.0))
+            }
+        };
+        let direction = within_cone(rng, self.direction, self.spread);
+        let Range { start, end } = self.speed;
+        let speed = start + (end - start) * scalar(rng.gen::<f64>());
+        Particle {
+            position: self.position + offset,
+            velocity: self.velocity + direction * speed,
+            age: V::Scalar::ZERO,
+            ..self.template.clone()
+        }
+    }
+}
+
+/// Uniformly distributed direction.
+fn unit<V: SimVector>(rng: &mut SimRng) -> V {
+    // 正規分布の成分を持つベクトルの向きは一様になる．
+    loop {
+        let v: V = V::from_fn(|_| V::Scalar::from_f64(rng.gaussian()));
+        let length = v.length();
+        if length > V::Scalar::ZERO {
+            return v / length;
+        }
+    }
+}
+
+/// Direction within `spread` radians of `axis`, uniform over the arc in the plane and over the
+/// spherical cap in space.
+fn within_cone<V: SimVector>(rng: &mut SimRng, axis: V, spread: f64) -> V {
+    let axis = axis / axis.length();
+    let spread = spread.clamp(0.0, PI);
+    let angle = if V::LEN == 2 {
+        spread * rng.gen::<f64>()
+    } else {
+        // 球冠の面積は cos の差に比例する．
+        (1.0 - (1.0 - spread.cos()) * rng.gen::<f64>()).acos()
+    };
+    // 軸に垂直な向きを一様に選ぶ．平面では左右のどちらか．
+    let sideways = loop {
+        let v: V = unit(rng);
+        let v = v - axis * v.dot(axis);
+        let length = v.length();
+        if length > V::Scalar::from_f64(1e-6) {
+            break v / length;
+        }
+    };
+    axis * V::Scalar::from_f64(angle.cos()) + sideways * V::Scalar::from_f64(angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use fixed_vector::VectorDot;
+
+    use super::*;
+    use crate::test_util::Free;
+    use crate::vector::Vector3;
+
+    fn spark(lifetime: Option<f64>) -> Particle<()> {
+        Particle {
+            props: (),
+            mass: 1.0,
+            position: Vector2 { x: 10.0, y: 20.0 },
+            velocity: Vector2::default(),
+            radius: None,
+            age: 0.0,
+            lifetime,
+        }
+    }
+
+    #[test]
+    fn test_emitted_particles_expire() {
+        let mut system = ParticleSystem::new(Free::<Vector2<f64>>(PhantomData), []);
+        let mut emitter = Emitter::new(spark(Some(1.0)), 30.0);
+
+        let mut first = Vec::new();
+        for step in 0..300 {
+            let ids = emitter.emit(&mut system, 0.01);
+            if step < 10 {
+                first.extend(ids);
+            }
+            system.update(0.01);
+        }
+
+        // 寿命 1 の間に 30 個ずつ生まれるので，常に 30 個前後が生きている．
+        assert!((29..=31).contains(&system.particles().len()));
+        assert!(!first.is_empty() && first.iter().all(|&id| system.get(id).is_none()));
+        assert!(system.particles().iter().all(|p| p.age < 1.0));
+    }
+
+    #[test]
+    fn test_shape_and_cone_bound_new_particles() {
+        let mut system = ParticleSystem::new(Free::<Vector2<f64>>(PhantomData), []);
+        let mut emitter = Emitter::new(spark(None), 0.0)
+            .with_seed(3)
+            .with_shape(SpawnShape::Ball { radius: 2.0 })
+            .with_velocity(Vector2 { x: 0.0, y: 3.0 }, 0.25, 5.0..6.0);
+        emitter.velocity = Vector2 { x: 1.0, y: 0.0 };
+
+        assert!(emitter.emit(&mut system, 1.0).is_empty());
+        let ids = emitter.burst(&mut system, 200);
+
+        assert_eq!(ids.len(), 200);
+        let mut angles = Vec::new();
+        for &id in &ids {
+            let p = system.get(id).unwrap();
+            assert!((p.position - emitter.position).length() <= 2.0);
+            let own = p.velocity - emitter.velocity;
+            assert!((5.0..=6.0 + 1e-12).contains(&own.length()));
+            angles.push(own.y.atan2(own.x) - PI / 2.0);
+        }
+        // 円錐の両側に広がる．
+        assert!(angles.iter().all(|a| a.abs() <= 0.25 + 1e-12));
+        assert!(angles.iter().any(|&a| a < -0.2) && angles.iter().any(|&a| a > 0.2));
+    }
+
+    /// Ideal gas in space, in single precision.
+    #[test]
+    fn test_emits_into_space() {
+        let spark = Particle {
+            props: (),
+            mass: 1.0,
+            position: Vector3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            velocity: Vector3::default(),
+            radius: None,
+            age: 0.0,
+            lifetime: None,
+        };
+        let axis = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let mut system = ParticleSystem::new(Free::<Vector3<f32>>(PhantomData), []);
+        let mut emitter = Emitter::new(spark, 0.0)
+            .with_seed(5)
+            .with_shape(SpawnShape::Sphere { radius: 2.0 })
+            .with_velocity(axis, 0.5, 1.0..1.0);
+        let ids = emitter.burst(&mut system, 500);
+
+        let mut cosines = Vec::new();
+        for &id in &ids {
+            let p = system.get(id).unwrap();
+            assert!(((p.position - emitter.position).length() - 2.0).abs() < 1e-5);
+            assert!((p.velocity.length() - 1.0).abs() < 1e-5);
+            cosines.push(p.velocity.dot(axis) as f64);
+        }
+        // 球冠で一様なら cos は [cos 0.5, 1] で一様になる．
+        let low = 0.5f64.cos();
+        assert!(cosines.iter().all(|&c| c >= low - 1e-5));
+        let mean = cosines.iter().sum::<f64>() / cosines.len() as f64;
+        assert!((mean - (1.0 + low) / 2.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle_param::rnd_vec;
+use crate::particle_system::Particle;
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// External force that only depends on the particle it acts on. Stack them in `ForceFields`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ForceField<V = Vector2<f64>> {
+    /// Uniform acceleration, so the force grows with the mass.
+    Gravity { acceleration: V },
+    /// Pulls towards `center` with `strength` times the mass over the squared distance, softened
+    /// within `radius` so that it stays finite at the centre. Negative strengths repel.
+    Attractor {
+        center: V,
+        strength: f64,
+        radius: f64,
+    },
+    /// Swirls around `center` within the plane of the first two axes, counterclockwise for positive
+    /// strengths; in space the axis of the vortex is parallel to the third. It grows linearly within
+    /// `radius` and falls off with the distance from the axis beyond.
+    Vortex {
+        center: V,
+        strength: f64,
+        radius: f64,
+    },
+    /// Drags particles towards `velocity`, perturbed by uniform noise of up to `turbulence` drawn
+    /// per particle and evaluation.
+    Wind {
+        velocity: V,
+        drag: f64,
+        turbulence: f64,
+    },
+    /// Uniform random force of up to `amplitude` in each axis, drawn anew on every evaluation
+    /// whatever the time step. Use a `Thermostat` to hold a temperature.
+    Noise { amplitude: f64 },
+    /// Proportional to the velocity, as in slow or viscous flow.
+    LinearDrag { coefficient: f64 },
+    /// Proportional to the squared speed, as in fast flow.
+    QuadraticDrag { coefficient: f64 },
+}
+
+impl<V: SimVector> ForceField<V> {
+    pub fn force<Props>(&self, p: &Particle<Props, V>, rng: &mut SimRng) -> V {
+        match *self {
+            ForceField::Gravity { acceleration } => acceleration * p.mass,
+            ForceField::Attractor {
+                center,
+                strength,
+                radius,
+            } => {
+                let [strength, radius] = [strength, radius].map(V::Scalar::from_f64);
+                let delta = center - p.position;
+                let r2 = delta.square_length() + radius * radius;
+                delta * (strength * p.mass / (r2 * r2.sqrt()))
+            }
+            ForceField::Vortex {
+                center,
+                strength,
+                radius,
+            } => {
+                let [strength, radius] = [strength, radius].map(V::Scalar::from_f64);
+                let delta = p.position - center;
+                let (dx, dy) = (*delta.get(0), *delta.get(1));
+                let r2 = dx * dx + dy * dy + radius * radius;
+                let tangent = V::from_fn(|i| match i {
+                    0 => -dy,
+                    1 => dx,
+                    _ => V::Scalar::ZERO,
+                });
+                tangent * (strength * p.mass / r2)
+            }
+            ForceField::Wind {
+                velocity,
+                drag,
+                turbulence,
+            } => (velocity + noise(rng, turbulence) - p.velocity) * V::Scalar::from_f64(drag),
+            ForceField::Noise { amplitude } => noise(rng, amplitude),
+            ForceField::LinearDrag { coefficient } => {
+                -p.velocity * V::Scalar::from_f64(coefficient)
+            }
+            ForceField::QuadraticDrag { coefficient } => {
+                -p.velocity * (V::Scalar::from_f64(coefficient) * p.velocity.length())
+            }
+        }
+    }
+}
+
+// 振幅 0 では乱数を引かない（空の範囲は `gen_range` が受け付けない）．
+fn noise<V: SimVector>(rng: &mut SimRng, amplitude: f64) -> V {
+    if amplitude > 0.0 {
+        rnd_vec(rng, -amplitude..amplitude)
+    } else {
+        V::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ForceLayer<V = Vector2<f64>> {
+    pub field: ForceField<V>,
+    /// Disabled layers are skipped, including their random numbers.
+    pub enabled: bool,
+}
+
+/// Stack of force fields summed in order. Layers can be added, edited and switched on and off
+/// between updates, see `ParticleSystem::params_mut`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForceFields<V = Vector2<f64>> {
+    pub layers: Vec<ForceLayer<V>>,
+}
+
+impl<V: SimVector> ForceFields<V> {
+    /// Adds an enabled layer on top.
+    pub fn with(mut self, field: ForceField<V>) -> Self {
+        self.push(field);
+        self
+    }
+
+    /// Adds an enabled layer on top and returns its index in `layers`.
+    pub fn push(&mut self, field: ForceField<V>) -> usize {
+        self.layers.push(ForceLayer {
+            field,
+            enabled: true,
+        });
+        self.layers.len() - 1
+    }
+
+    pub fn force<Props>(&self, p: &Particle<Props, V>, rng: &mut SimRng) -> V {
+        self.layers
+            .iter()
+            .filter(|layer| layer.enabled)
+            .fold(V::default(), |f, layer| f + layer.field.force(p, rng))
+    }
+}
+
+impl<V: SimVector> FromIterator<ForceField<V>> for ForceFields<V> {
+    fn from_iter<T: IntoIterator<Item = ForceField<V>>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(ForceFields::default(), ForceFields::with)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fixed_vector::VectorDot;
+
+    use super::*;
+    use crate::particle_param::ParticleParam;
+    use crate::particle_system::ParticleSystem;
+    use crate::test_util::{scalar_tests, tolerance};
+
+    fn v<S: Scalar>(x: f64, y: f64) -> Vector2<S> {
+        Vector2 {
+            x: S::from_f64(x),
+            y: S::from_f64(y),
+        }
+    }
+
+    fn particle<S: Scalar>(x: f64, y: f64, vx: f64, vy: f64) -> Particle<usize, Vector2<S>> {
+        Particle {
+            props: 0,
+            mass: S::from_f64(2.0),
+            position: v(x, y),
+            velocity: v(vx, vy),
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        }
+    }
+
+    fn test_primitives_point_the_right_way<S: Scalar>() {
+        let tolerance = tolerance::<S>();
+        let zero = S::ZERO;
+        let mut rng = SimRng::new(0);
+        let p = particle::<S>(3.0, 4.0, 1.0, 0.0);
+        let mut force = |field: ForceField<Vector2<S>>| field.force(&p, &mut rng);
+        let origin = Vector2::default();
+
+        let g = force(ForceField::Gravity {
+            acceleration: v(0.0, -9.8),
+        });
+        assert_eq!((g.x, g.y), (zero, S::from_f64(-19.6)));
+
+        let attract = force(ForceField::Attractor {
+            center: origin,
+            strength: 5.0,
+            radius: 0.0,
+        });
+        assert!((attract.length().to_f64() - 2.0 * 5.0 / 25.0).abs() < tolerance);
+        assert!(attract.x < zero && attract.y < zero);
+        let repel = force(ForceField::Attractor {
+            center: origin,
+            strength: -5.0,
+            radius: 0.0,
+        });
+        assert!(repel.x > zero && repel.y > zero);
+
+        // 反時計回りなので，中心から見た位置ベクトルと直交し左を向く．
+        let swirl = force(ForceField::Vortex {
+            center: origin,
+            strength: 1.0,
+            radius: 1.0,
+        });
+        assert!(swirl.dot(p.position).abs().to_f64() < tolerance);
+        assert!(p.position.x * swirl.y - p.position.y * swirl.x > zero);
+
+        let calm = force(ForceField::Wind {
+            velocity: v(1.0, 0.0),
+            drag: 1.0,
+            turbulence: 0.0,
+        });
+        assert_eq!(calm.length(), zero);
+        let gust = force(ForceField::Wind {
+            velocity: v(1.0, 0.0),
+            drag: 1.0,
+            turbulence: 0.5,
+        });
+        let half = S::from_f64(0.5);
+        assert!(gust.x.abs() <= half && gust.y.abs() <= half && gust.length() > zero);
+
+        let linear = force(ForceField::LinearDrag { coefficient: 0.5 });
+        let quadratic = force(ForceField::QuadraticDrag { coefficient: 0.5 });
+        assert_eq!((linear.x, quadratic.x), (-half, -half));
+    }
+
+    /// `fields` on particles that do not interact with each other.
+    fn inert<S: Scalar>(fields: ForceFields<Vector2<S>>) -> ParticleParam<Vector2<S>> {
+        ParticleParam {
+            fields,
+            params: [((0, 0), Vector2::default())].into_iter().collect(),
+        }
+    }
+
+    fn test_fields_act_once_whatever_the_neighbours<S: Scalar>() {
+        let gravity = ForceField::Gravity {
+            acceleration: v(0.0, -10.0),
+        };
+        let particles = [
+            particle::<S>(1000.0, 0.0, 0.0, 0.0),
+            particle(0.0, 0.0, 0.0, 0.0),
+            particle(10.0, 0.0, 0.0, 0.0),
+            particle(0.0, 10.0, 0.0, 0.0),
+            particle(-10.0, 0.0, 0.0, 0.0),
+        ];
+        let mut system =
+            ParticleSystem::new(inert(ForceFields::default().with(gravity)), particles);
+        system.update(S::from_f64(0.1));
+
+        // 孤立した粒子も近傍の多い粒子も同じだけ落ちる．
+        let tolerance = tolerance::<S>();
+        for p in system.particles() {
+            let Vector2 { x, y } = p.velocity;
+            assert!(x.to_f64().abs() < tolerance && (y.to_f64() + 1.0).abs() < tolerance);
+        }
+    }
+
+    fn test_layers_can_be_toggled_between_updates<S: Scalar>() {
+        let fields: ForceFields<Vector2<S>> = [
+            ForceField::Gravity {
+                acceleration: v(0.0, -10.0),
+            },
+            ForceField::LinearDrag { coefficient: 4.0 },
+        ]
+        .into_iter()
+        .collect();
+        let mut system = ParticleSystem::new(inert(fields), [particle(0.0, 0.0, 0.0, 0.0)]);
+        // f32 では終端速度の桁で丸めが効く．
+        let tolerance = tolerance::<S>().max(1e-6);
+
+        // 終端速度 m g / c に落ち着く．
+        for _ in 0..1000 {
+            system.update(S::from_f64(0.01));
+        }
+        let Vector2 { x, y } = system.particles()[0].velocity;
+        assert!(x.to_f64().abs() < 1e-12 && (y.to_f64() + 5.0).abs() < tolerance);
+
+        system.params_mut().fields.layers[0].enabled = false;
+        for _ in 0..1000 {
+            system.update(S::from_f64(0.01));
+        }
+        assert!(system.particles()[0].velocity.length().to_f64() < tolerance);
+    }
+
+    scalar_tests!(
+        test_primitives_point_the_right_way,
+        test_fields_act_once_whatever_the_neighbours,
+        test_layers_can_be_toggled_between_updates,
+    );
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::SimVector;
+
+/// Advances particles by one step given a way to evaluate their accelerations.
+/// `acceleration` fills one entry per particle of the slice it is handed.
+pub trait Integrator {
+    fn step<Props: Clone, V: SimVector>(
+        &self,
+        particles: &mut [Particle<Props, V>],
+        delta_time: V::Scalar,
+        acceleration: impl FnMut(&[Particle<Props, V>], &mut [V]),
+    );
+}
+
+/// Semi-implicit Euler: kicks velocity first, then drifts with the new velocity.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SymplecticEuler;
+
+/// Kick-drift-kick Velocity Verlet. Accelerations are re-evaluated at the start of every step
+/// so that particles may be added or removed between steps.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct VelocityVerlet;
+
+/// Classic fourth order Runge-Kutta on positions and velocities.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RungeKutta4;
+
+fn zeros<V: SimVector>(len: usize) -> Vec<V> {
+    vec![V::default(); len]
+}
+
+impl Integrator for SymplecticEuler {
+    fn step<Props: Clone, V: SimVector>(
+        &self,
+        particles: &mut [Particle<Props, V>],
+        delta_time: V::Scalar,
+        mut acceleration: impl FnMut(&[Particle<Props, V>], &mut [V]),
+    ) {
+        let mut a = zeros(particles.len());
+        acceleration(particles, &mut a);
+        for (p, a) in particles.iter_mut().zip(a) {
+            p.velocity += a * delta_time;
+            p.position += p.velocity * delta_time;
+        }
+    }
+}
+
+impl Integrator for VelocityVerlet {
+    fn step<Props: Clone, V: SimVector>(
+        &self,
+        particles: &mut [Particle<Props, V>],
+        delta_time: V::Scalar,
+        mut acceleration: impl FnMut(&[Particle<Props, V>], &mut [V]),
+    ) {
+        let half = delta_time / V::Scalar::from_f64(2.0);
+        let mut a = zeros(particles.len());
+        acceleration(particles, &mut a);
+        for (p, a) in particles.iter_mut().zip(&a) {
+            p.velocity += *a * half;
+            p.position += p.velocity * delta_time;
+        }
+        acceleration(particles, &mut a);
+        for (p, a) in particles.iter_mut().zip(a) {
+            p.velocity += a * half;
+        }
+    }
+}
+
+impl Integrator for RungeKutta4 {
+    fn step<Props: Clone, V: SimVector>(
+        &self,
+        particles: &mut [Particle<Props, V>],
+        delta_time: V::Scalar,
+        mut acceleration: impl FnMut(&[Particle<Props, V>], &mut [V]),
+    ) {
+        let initial = particles.to_vec();
+        let mut stage = particles.to_vec();
+        let mut a = zeros(particles.len());
+        let mut sum_x = zeros::<V>(particles.len());
+        let mut sum_v = zeros::<V>(particles.len());
+
+        for (weight, offset) in [(1.0, 0.5), (2.0, 0.5), (2.0, 1.0), (1.0, 0.0)] {
+            let (weight, offset) = (V::Scalar::from_f64(weight), V::Scalar::from_f64(offset));
+            acceleration(&stage, &mut a);
+            for i in 0..stage.len() {
+                let (k_x, k_v) = (stage[i].velocity, a[i]);
+                sum_x[i] += k_x * weight;
+                sum_v[i] += k_v * weight;
+                stage[i].position = initial[i].position + k_x * (delta_time * offset);
+                stage[i].velocity = initial[i].velocity + k_v * (delta_time * offset);
+            }
+        }
+
+        let sixth = delta_time / V::Scalar::from_f64(6.0);
+        for (i, p) in particles.iter_mut().enumerate() {
+            p.position += sum_x[i] * sixth;
+            p.velocity += sum_v[i] * sixth;
+        }
+    }
+}
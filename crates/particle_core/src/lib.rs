@@ -0,0 +1,23 @@
+pub mod adaptive_step;
+pub mod barnes_hut;
+pub mod bond;
+pub mod boundary;
+pub mod clock;
+pub mod collision;
+pub mod combinators;
+pub mod constraint;
+pub mod diagnostics;
+pub mod emitter;
+pub mod force_field;
+pub mod integrator;
+pub mod particle_id;
+pub mod particle_param;
+pub mod particle_system;
+pub mod potential;
+pub mod rng;
+pub mod scalar;
+pub mod spatial_grid;
+#[cfg(test)]
+mod test_util;
+pub mod thermostat;
+pub mod vector;
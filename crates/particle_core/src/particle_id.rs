@@ -0,0 +1,108 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Handle to a particle that stays valid across updates until the particle is despawned.
+/// Slots are reused, so a handle also carries the generation it was issued for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ParticleId {
+    index: u32,
+    generation: u32,
+}
+
+/// Written as `<index>v<generation>`.
+impl fmt::Display for ParticleId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    dense: Option<usize>,
+}
+
+/// Maps `ParticleId`s to positions in a densely packed particle array.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParticleSlots {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    ids: Vec<ParticleId>,
+}
+
+impl ParticleSlots {
+    /// Issues an id for a particle appended at the end of the dense array.
+    pub fn insert(&mut self) -> ParticleId {
+        let dense = Some(self.ids.len());
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.dense = dense;
+                ParticleId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    dense,
+                });
+                ParticleId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.ids.push(id);
+        id
+    }
+
+    pub fn index_of(&self, id: ParticleId) -> Option<usize> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation == id.generation {
+            slot.dense
+        } else {
+            None
+        }
+    }
+
+    /// Releases `id` and returns the dense index the caller has to `swap_remove`.
+    pub fn swap_remove(&mut self, id: ParticleId) -> Option<usize> {
+        let dense = self.index_of(id)?;
+        self.release(id);
+        self.ids.swap_remove(dense);
+        if let Some(moved) = self.ids.get(dense) {
+            self.slots[moved.index as usize].dense = Some(dense);
+        }
+        Some(dense)
+    }
+
+    /// Releases every id whose flag in `keep` is false, mirroring `Vec::retain` on the dense array.
+    pub fn retain(&mut self, keep: &[bool]) {
+        let mut dense = 0;
+        for (i, &keep) in keep.iter().enumerate() {
+            let id = self.ids[i];
+            if keep {
+                self.slots[id.index as usize].dense = Some(dense);
+                self.ids[dense] = id;
+                dense += 1;
+            } else {
+                self.release(id);
+            }
+        }
+        self.ids.truncate(dense);
+    }
+
+    pub fn ids(&self) -> &[ParticleId] {
+        &self.ids
+    }
+
+    fn release(&mut self, id: ParticleId) {
+        let slot = &mut self.slots[id.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.dense = None;
+        self.free.push(id.index);
+    }
+}
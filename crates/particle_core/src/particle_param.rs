@@ -0,0 +1,364 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_step::AdaptiveStep;
+use crate::boundary::{Boundary, Domain};
+use crate::force_field::ForceFields;
+use crate::particle_system::{Particle, ParticleSystem, ParticleSystemParameters};
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::thermostat::Thermostat;
+use crate::vector::{SimVector, Vector2};
+
+pub static D_0: f64 = 30.0;
+pub static D_1: f64 = 60.0;
+pub static D_MAX: f64 = 120.0;
+
+// ポテンシャルベースの計算もありかも．でもポテンシャルだけだと電磁気力を表現できない．
+/// Interactions between kinds of particles, the kind being the props.
+/// `params[(k0, k1)]` holds the repulsion below `D_0` in `x` and the attraction up to `D_MAX` in `y`.
+/// The rules only depend on distances, so they run unchanged in any dimension `V`.
+#[derive(Serialize, Deserialize)]
+pub struct ParticleParam<V = Vector2<f64>> {
+    /// External forces on every particle, felt once per evaluation however many neighbours it has.
+    /// Drag and noise tuned when they acted once per neighbour need dividing by that count.
+    pub fields: ForceFields<V>,
+    pub params: FrozenSortedMap<(usize, usize), Vector2<f64>>,
+}
+
+impl<V: SimVector> ParticleSystemParameters for ParticleParam<V> {
+    type Props = usize;
+    type Vector = V;
+
+    fn external_force(
+        &self,
+        p: &Particle<Self::Props, V>,
+        _delta_time: V::Scalar,
+        rng: &mut SimRng,
+    ) -> V {
+        self.fields.force(p, rng)
+    }
+
+    fn internal_force(
+        &self,
+        p_target: &Particle<Self::Props, V>,
+        p_other: &Particle<Self::Props, V>,
+        _delta_time: V::Scalar,
+    ) -> V::Scalar {
+        let params = self
+            .params
+            .get(&(p_target.props, p_other.props))
+            .or_else(|| self.params.get(&(p_other.props, p_target.props)))
+            .unwrap()
+            .to_owned();
+
+        let distance = (p_other.position - p_target.position).length().to_f64();
+        let force = if distance < D_0 {
+            params.x * (distance - D_0)
+        } else if distance < D_1 {
+            params.y * (distance - D_0)
+        } else if distance < D_MAX {
+            params.y * (D_1 - D_0) * (D_MAX - distance) / (D_MAX - D_1)
+        } else {
+            0.0
+        };
+        V::Scalar::from_f64(force)
+    }
+
+    fn cutoff_radius(&self) -> Option<V::Scalar> {
+        Some(V::Scalar::from_f64(D_MAX))
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
+}
+
+/// `per_kind` resting particles of each of `kinds` kinds scattered over a periodic square of side
+/// `size`, or a cube for `Vector3<f64>`, with random interactions between every pair of kinds.
+/// Everything is drawn from `seed`. A Langevin thermostat keeps the particles moving; change its
+/// temperature through `thermostat_mut`.
+pub fn random_scenario<V: SimVector>(
+    kinds: usize,
+    per_kind: usize,
+    size: f64,
+    seed: u64,
+) -> ParticleSystem<ParticleParam<V>> {
+    let mut rng = SimRng::new(seed);
+    let ps: Vec<_> = (0..kinds * per_kind)
+        .map(|i| Particle {
+            props: i / per_kind,
+            mass: V::Scalar::ONE,
+            position: rnd_vec(&mut rng, 0.0..size),
+            velocity: V::default(),
+            radius: None,
+            age: V::Scalar::ZERO,
+            lifetime: None,
+        })
+        .collect();
+    let params = (0..kinds)
+        .flat_map(|k0| (k0..kinds).map(move |k1| (k0, k1)))
+        .map(|key| (key, rnd_vec::<Vector2<f64>>(&mut rng, 0.0..1.0)))
+        .collect::<FrozenSortedMap<_, _>>();
+
+    ParticleSystem::new(
+        ParticleParam {
+            fields: ForceFields::default(),
+            params,
+        },
+        ps,
+    )
+    .with_seed(seed)
+    // 以前の抵抗係数 0.01 と一様ノイズ（分散 1/3）はどちらも近傍ごとにかかっていたので，
+    // 温度は近傍の数によらず毎秒 60 回の更新で dt / (6 × 0.01) になり，数十の近傍の分だけ
+    // 強い抵抗で 1 秒ほどで冷えていた．その温度と時間に合わせる．
+    .with_thermostat(Thermostat::Langevin {
+        temperature: (1.0 / 60.0) / (6.0 * 0.01),
+        coupling_time: 1.0,
+    })
+    .with_boundary(Boundary::Periodic(Domain {
+        min: V::default(),
+        max: V::from_fn(|_| V::Scalar::from_f64(size)),
+    }))
+}
+
+/// Substeps for `ParticleParam` in which no particle covers more than a tenth of the repulsive
+/// range `D_0`, so that strong rules cannot throw particles through each other.
+pub fn adaptive_step() -> AdaptiveStep {
+    AdaptiveStep::new(0.1 * D_0)
+}
+
+pub fn rnd_vec<V: SimVector>(rng: &mut impl Rng, range: std::ops::Range<f64>) -> V {
+    V::from_fn(|_| V::Scalar::from_f64(rng.gen_range(range.clone())))
+}
+
+pub fn rnd(rng: &mut impl Rng, range: std::ops::Range<f64>) -> f64 {
+    rng.gen_range(range)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FrozenSortedMap<K: Ord, V> {
+    vec: Vec<(K, V)>,
+}
+
+impl<K: Ord + Copy, V> FromIterator<(K, V)> for FrozenSortedMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut vec = iter.into_iter().collect::<Vec<_>>();
+        vec.sort_by_key(|pair| pair.0);
+
+        FrozenSortedMap { vec }
+    }
+}
+
+impl<K: Ord + Copy, V> FrozenSortedMap<K, V> {
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let index = self.vec.binary_search_by_key(key, |pair| pair.0);
+        index.ok().map(|i| &self.vec[i].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    fn v<T>(x: T, y: T) -> Vector2<T> {
+        Vector2 { x, y }
+    }
+
+    fn particle(kind: usize, x: f64) -> Particle<usize> {
+        Particle {
+            props: kind,
+            mass: 1.0,
+            position: v(x, 0.0),
+            velocity: v(0.0, 0.0),
+            radius: None,
+            age: 0.0,
+            lifetime: None,
+        }
+    }
+
+    #[test]
+    fn test_force_curve_is_continuous_and_symmetric() {
+        let param = ParticleParam {
+            fields: ForceFields::default(),
+            params: [((0, 0), v(1.0, 0.5)), ((0, 1), v(2.0, -0.5))]
+                .into_iter()
+                .collect(),
+        };
+        let force =
+            |k0, k1, d: f64| param.internal_force(&particle(k0, 0.0), &particle(k1, d), 0.0);
+
+        for d in [D_0, D_1, D_MAX] {
+            assert!((force(0, 0, d - 1e-9) - force(0, 0, d + 1e-9)).abs() < 1e-6);
+        }
+        assert_eq!(force(0, 0, 0.0), -D_0);
+        assert_eq!(force(0, 0, D_1), 0.5 * (D_1 - D_0));
+        assert_eq!(force(0, 0, D_MAX), 0.0);
+        assert_eq!(force(0, 1, 45.0), force(1, 0, 45.0));
+        assert!(param.params.get(&(1, 1)).is_none());
+    }
+
+    #[test]
+    fn test_rules_run_in_three_dimensions() {
+        let mut system = random_scenario::<Vector3<f64>>(3, 20, 300.0, 1);
+        let start: Vec<_> = system.particles().iter().map(|p| p.position.z).collect();
+        for _ in 0..50 {
+            system.update(0.1);
+        }
+
+        assert_eq!(system.particles().len(), 60);
+        for (p, z) in system.particles().iter().zip(start) {
+            let Vector3 { x, y, z: z1 } = p.position;
+            assert!([x, y, z1].iter().all(|c| (0.0..300.0).contains(c)));
+            assert_ne!(z, z1);
+        }
+    }
+
+    #[test]
+    fn test_scene_settles_within_a_minute() {
+        // キャンバスの場面と同じ密度で，粒子は半分．
+        let mut system = random_scenario::<Vector2<f64>>(6, 15, 354.0, 0);
+        let target = system.thermostat_mut().unwrap().temperature();
+        for _ in 0..60 * 20 {
+            system.update(1.0 / 20.0);
+        }
+
+        // 集まるときに解放されたエネルギーが熱浴に逃げ切っている．
+        let temperature = system.diagnostics().temperature;
+        assert!((temperature / target - 1.0).abs() < 0.5, "{temperature}");
+    }
+
+    #[test]
+    fn test_temperature_controls_the_scene() {
+        let mut measured = Vec::new();
+        for target in [0.5, 2.0] {
+            // 粒子がまばらなので，相互作用せずに熱浴だけで温度が決まる．
+            let mut system = random_scenario::<Vector2<f64>>(3, 200, 1e6, 3);
+            if let Some(Thermostat::Langevin { temperature, .. }) = system.thermostat_mut() {
+                *temperature = target;
+            }
+            // 緩和時間 100 の数倍待ってから平均する．
+            for _ in 0..300 {
+                system.update(1.0);
+            }
+            let mut sum = 0.0;
+            for _ in 0..10 {
+                for _ in 0..20 {
+                    system.update(1.0);
+                }
+                sum += system.diagnostics().temperature;
+            }
+
+            let temperature = sum / 10.0;
+            assert!((temperature / target - 1.0).abs() < 0.1, "{temperature}");
+            measured.push(temperature);
+        }
+
+        assert!(measured[1] > 3.0 * measured[0]);
+    }
+
+    /// `ParticleParam` evaluated from both sides of every pair.
+    struct Pairwise(ParticleParam);
+
+    impl ParticleSystemParameters for Pairwise {
+        type Props = usize;
+        type Vector = Vector2<f64>;
+
+        fn external_force(
+            &self,
+            p: &Particle<usize>,
+            delta_time: f64,
+            rng: &mut SimRng,
+        ) -> Vector2<f64> {
+            self.0.external_force(p, delta_time, rng)
+        }
+
+        fn internal_force(
+            &self,
+            p_target: &Particle<usize>,
+            p_other: &Particle<usize>,
+            delta_time: f64,
+        ) -> f64 {
+            self.0.internal_force(p_target, p_other, delta_time)
+        }
+
+        fn cutoff_radius(&self) -> Option<f64> {
+            self.0.cutoff_radius()
+        }
+    }
+
+    fn scenario_positions<P: ParticleSystemParameters<Vector = Vector2<f64>>>(
+        system: &mut ParticleSystem<P>,
+    ) -> Vec<(f64, f64)> {
+        for _ in 0..60 {
+            system.update(1.0 / 60.0);
+        }
+        system
+            .particles()
+            .iter()
+            .map(|p| (p.position.x, p.position.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_symmetric_path_follows_pairwise() {
+        let mut symmetric = random_scenario(6, 30, 500.0, 0);
+        let particles = symmetric.particles().to_vec();
+        let params = ParticleParam {
+            fields: ForceFields::default(),
+            params: symmetric.params().params.vec.iter().copied().collect(),
+        };
+        let mut pairwise = ParticleSystem::new(Pairwise(params), particles)
+            .with_seed(0)
+            .with_thermostat(*symmetric.thermostat_mut().unwrap())
+            .with_boundary(Boundary::Periodic(Domain {
+                min: v(0.0, 0.0),
+                max: v(500.0, 500.0),
+            }));
+
+        // 足す順が違うので丸め誤差の分だけずれる．
+        let a = scenario_positions(&mut symmetric);
+        let b = scenario_positions(&mut pairwise);
+        for ((x0, y0), (x1, y1)) in a.into_iter().zip(b) {
+            assert!((x0 - x1).hypot(y0 - y1) < 1e-6);
+        }
+    }
+
+    /// The feature only changes how the work is spread, so one thread stands in for a build without
+    /// it.
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_scenario_does_not_depend_on_threads() {
+        let run = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut system = random_scenario(6, 30, 500.0, 0);
+            pool.install(|| scenario_positions(&mut system))
+        };
+
+        assert_eq!(run(1), run(4));
+    }
+
+    #[test]
+    fn test_single_precision_follows_double_precision() {
+        let mut single = random_scenario::<Vector2<f32>>(3, 20, 300.0, 2);
+        let mut double = random_scenario::<Vector2<f64>>(3, 20, 300.0, 2);
+        for _ in 0..20 {
+            single.update(0.1);
+            double.update(0.1);
+        }
+
+        // 乱数は同じ列から引くので，丸め誤差の分しかずれない．
+        let boundary = Boundary::Periodic(Domain {
+            min: v(0.0, 0.0),
+            max: v(300.0, 300.0),
+        });
+        for (s, d) in single.particles().iter().zip(double.particles()) {
+            let s = v(s.position.x as f64, s.position.y as f64);
+            assert!(boundary.separation(s, d.position).length() < 1e-2);
+        }
+    }
+}
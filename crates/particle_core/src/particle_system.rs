@@ -0,0 +1,1643 @@
+use std::{borrow::Cow, collections::BTreeMap, mem::swap};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::adaptive_step::AdaptiveStep;
+use crate::barnes_hut::BarnesHutTree;
+use crate::bond::Bond;
+use crate::boundary::Boundary;
+use crate::collision::Collisions;
+use crate::constraint::{Constraint, ConstraintSolver};
+use crate::diagnostics::Diagnostics;
+use crate::integrator::{Integrator, SymplecticEuler};
+use crate::particle_id::{ParticleId, ParticleSlots};
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::spatial_grid::{Neighborhood, SpatialGrid};
+use crate::thermostat::Thermostat;
+use crate::vector::{SimVector, Vector2};
+
+/// Serializing captures the complete state, including the random number stream, so a restored
+/// system continues exactly like the original. See `to_json` and `to_bytes`.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "P: Serialize, P::Props: Serialize, I: Serialize",
+    deserialize = "P: Deserialize<'de>, P::Props: Deserialize<'de>, I: Deserialize<'de>"
+))]
+pub struct ParticleSystem<P: ParticleSystemParameters, I: Integrator = SymplecticEuler> {
+    #[serde(rename = "particles")]
+    particles0: Vec<Particle<P::Props, P::Vector>>,
+    #[serde(rename = "previous")]
+    particles1: Vec<Particle<P::Props, P::Vector>>,
+    slots: ParticleSlots,
+    params: P,
+    #[serde(skip)]
+    grid: SpatialGrid<P::Vector>,
+    #[serde(skip)]
+    tree: BarnesHutTree<P::Vector>,
+    integrator: I,
+    boundary: Boundary<P::Vector>,
+    collisions: Option<Collisions>,
+    thermostat: Option<Thermostat>,
+    adaptive_step: Option<AdaptiveStep>,
+    opening_angle: Option<f64>,
+    bonds: Vec<Bond>,
+    #[serde(skip)]
+    broken_bonds: Vec<Bond>,
+    constraints: Vec<Constraint<P::Vector>>,
+    solver: ConstraintSolver,
+    seed: u64,
+    evaluations: u64,
+}
+
+/// `Sync` with the `parallel` feature, since parameters and particles are then shared between threads.
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "parallel")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+pub trait ParticleSystemParameters: MaybeSync {
+    type Props: Clone + MaybeSync;
+    /// `Vector2<f64>` for simulations in the plane, `Vector3<f64>` for simulations in space. Its
+    /// `Scalar` is also the type of masses, forces and time steps, see `ScalarOf`.
+    type Vector: SimVector;
+    /// Force from outside the system, such as gravity or drag, felt by `p` once per evaluation.
+    /// `rng` is owned by the system and seeded per particle and evaluation, see `with_seed`.
+    fn external_force(
+        &self,
+        p: &Particle<Self::Props, Self::Vector>,
+        delta_time: ScalarOf<Self>,
+        rng: &mut SimRng,
+    ) -> Self::Vector;
+    /// Attraction of `p_target` towards `p_other` along the line between them. Negative values repel.
+    fn internal_force(
+        &self,
+        p_target: &Particle<Self::Props, Self::Vector>,
+        p_other: &Particle<Self::Props, Self::Vector>,
+        delta_time: ScalarOf<Self>,
+    ) -> ScalarOf<Self> {
+        let _ = (p_target, p_other, delta_time);
+        ScalarOf::<Self>::ZERO
+    }
+
+    /// Full force exerted on `p_target` by `p_other`, which accelerates it by the force over its
+    /// own mass. Defaults to `internal_force` along the line between them; override to express
+    /// forces with a tangential or velocity dependent part.
+    fn internal_force_vector(
+        &self,
+        p_target: &Particle<Self::Props, Self::Vector>,
+        p_other: &Particle<Self::Props, Self::Vector>,
+        delta_time: ScalarOf<Self>,
+    ) -> Self::Vector {
+        let delta = p_other.position - p_target.position;
+        delta / delta.length() * self.internal_force(p_target, p_other, delta_time)
+    }
+
+    /// Distance at and beyond which two particles do not interact at all.
+    /// When given, `update` only visits neighbouring particles instead of every pair.
+    fn cutoff_radius(&self) -> Option<ScalarOf<Self>> {
+        None
+    }
+
+    /// Whether `internal_force_vector(a, b)` is always `-internal_force_vector(b, a)`. When it is,
+    /// `update` evaluates every pair once instead of once from each side, which halves the calls
+    /// to `internal_force_vector`. Like the other, it spreads over threads with the `parallel`
+    /// feature and gives the same result on any number of them.
+    fn symmetric(&self) -> bool {
+        false
+    }
+
+    /// Number of independent long-range fields, such as two for gravity and Coulomb forces
+    /// together. Under `with_barnes_hut` each one is summed over its own tree.
+    fn source_fields(&self) -> usize {
+        1
+    }
+
+    /// Strength of `p` as the source of the long-range `field`, such as its mass for gravity or its
+    /// charge for Coulomb forces. Only used by `with_barnes_hut`.
+    fn source_strength(
+        &self,
+        p: &Particle<Self::Props, Self::Vector>,
+        field: usize,
+    ) -> ScalarOf<Self> {
+        let _ = (p, field);
+        ScalarOf::<Self>::ZERO
+    }
+
+    /// Force of `field` on `p_target` from a point source of `strength` at `position`. Under
+    /// `with_barnes_hut` the source is either a single particle or a whole distant cluster.
+    fn source_force(
+        &self,
+        p_target: &Particle<Self::Props, Self::Vector>,
+        position: Self::Vector,
+        strength: ScalarOf<Self>,
+        field: usize,
+    ) -> Self::Vector {
+        let _ = (p_target, position, strength, field);
+        Self::Vector::default()
+    }
+
+    /// Potential energy stored between the two particles, if the interaction is conservative.
+    fn pair_potential(
+        &self,
+        p_target: &Particle<Self::Props, Self::Vector>,
+        p_other: &Particle<Self::Props, Self::Vector>,
+    ) -> Option<ScalarOf<Self>> {
+        let _ = (p_target, p_other);
+        None
+    }
+
+    /// Called after every step with the time step just taken, for parameters that change over
+    /// time. Under `with_adaptive_step` that is once per substep of `update`.
+    fn advance(&mut self, delta_time: ScalarOf<Self>) {
+        let _ = delta_time;
+    }
+}
+
+/// Scalar type of the particles of `P`.
+pub type ScalarOf<P> = <<P as ParticleSystemParameters>::Vector as SimVector>::Scalar;
+
+/// `p1` moved to its image closest to `p0`, so that parameters can measure distances directly.
+fn nearest_image<'a, Props: Clone, V: SimVector>(
+    boundary: &Boundary<V>,
+    p0: &Particle<Props, V>,
+    p1: &'a Particle<Props, V>,
+) -> Cow<'a, Particle<Props, V>> {
+    if boundary.is_periodic() {
+        Cow::Owned(Particle {
+            position: p0.position + boundary.separation(p0.position, p1.position),
+            ..p1.clone()
+        })
+    } else {
+        Cow::Borrowed(p1)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Props: Serialize",
+    deserialize = "Props: Deserialize<'de>"
+))]
+pub struct Particle<Props, V: SimVector = Vector2<f64>> {
+    pub props: Props,
+    pub mass: V::Scalar,
+    pub position: V,
+    pub velocity: V,
+    /// Size for hard-sphere collisions. Particles without one are points that never collide.
+    pub radius: Option<V::Scalar>,
+    /// Time since the particle was created, advanced by `update`.
+    #[serde(default)]
+    pub age: V::Scalar,
+    /// Age at which `update` removes the particle. Particles without one live forever.
+    #[serde(default)]
+    pub lifetime: Option<V::Scalar>,
+}
+
+impl<Props, V: SimVector> Particle<Props, V> {
+    pub fn is_expired(&self) -> bool {
+        self.lifetime.is_some_and(|lifetime| self.age >= lifetime)
+    }
+}
+
+/// What one evaluation of the accelerations reads besides the particles themselves.
+struct Evaluation<'a, P: ParticleSystemParameters> {
+    params: &'a P,
+    boundary: &'a Boundary<P::Vector>,
+    opening_angle: Option<f64>,
+    /// With the indices of both ends in the particles being evaluated.
+    bonds: &'a [(usize, usize, Bond)],
+    delta_time: ScalarOf<P>,
+    seed: u64,
+    index: u64,
+}
+
+/// Sets `output[i] = f(i)` for every `i`, on several threads with the `parallel` feature.
+fn for_each_output<T: Send>(output: &mut [T], f: impl Fn(usize) -> T + MaybeSync) {
+    // 各要素は自分の乱数列と出力しか触らないので，並列でも結果は逐次と同じになる．
+    #[cfg(feature = "parallel")]
+    output
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, o)| *o = f(i));
+    #[cfg(not(feature = "parallel"))]
+    for (i, o) in output.iter_mut().enumerate() {
+        *o = f(i);
+    }
+}
+
+impl<P: ParticleSystemParameters> Evaluation<'_, P> {
+    /// Internal force on `p0` from `p1`, or `None` for coincident particles, which do not interact.
+    fn pair_force(
+        &self,
+        p0: &Particle<P::Props, P::Vector>,
+        p1: &Particle<P::Props, P::Vector>,
+    ) -> Option<P::Vector> {
+        let delta = self.boundary.separation(p0.position, p1.position);
+        if delta.square_length() < ScalarOf::<P>::from_f64(0.0001) {
+            return None;
+        }
+        let p1 = &*nearest_image(self.boundary, p0, p1);
+        Some(self.params.internal_force_vector(p0, p1, self.delta_time))
+    }
+
+    /// Acceleration of `p0` from its internal force with `p1`.
+    fn pair_acceleration(
+        &self,
+        p0: &Particle<P::Props, P::Vector>,
+        p1: &Particle<P::Props, P::Vector>,
+    ) -> P::Vector {
+        self.pair_force(p0, p1)
+            .map_or(P::Vector::default(), |force| force / p0.mass)
+    }
+
+    /// Acceleration of the `i`th particle from `external_force`, drawn from its own stream.
+    fn external_acceleration(
+        &self,
+        particles: &[Particle<P::Props, P::Vector>],
+        i: usize,
+    ) -> P::Vector {
+        let p = &particles[i];
+        let mut rng = SimRng::for_stream(self.seed, self.index, i as u64);
+        self.params.external_force(p, self.delta_time, &mut rng) / p.mass
+    }
+
+    fn accelerations(
+        &self,
+        grid: &mut SpatialGrid<P::Vector>,
+        tree: &mut BarnesHutTree<P::Vector>,
+        particles: &[Particle<P::Props, P::Vector>],
+        accelerations: &mut [P::Vector],
+    ) {
+        if let Some(theta) = self.opening_angle {
+            self.far_field_accelerations(theta, tree, particles, accelerations);
+        } else {
+            let neighbors =
+                Neighborhood::new(self.params.cutoff_radius(), self.boundary, grid, particles);
+            if self.params.symmetric() {
+                self.symmetric_accelerations(&neighbors, particles, accelerations);
+            } else {
+                self.pairwise_accelerations(&neighbors, particles, accelerations);
+            }
+        }
+
+        for &(i, j, bond) in self.bonds {
+            let force = bond.force(self.boundary, &particles[i], &particles[j]);
+            accelerations[i] += force / particles[i].mass;
+            accelerations[j] -= force / particles[j].mass;
+        }
+    }
+
+    fn pairwise_accelerations(
+        &self,
+        neighbors: &Neighborhood<P::Vector>,
+        particles: &[Particle<P::Props, P::Vector>],
+        accelerations: &mut [P::Vector],
+    ) {
+        for_each_output(accelerations, |i| {
+            let p0 = &particles[i];
+            let mut a = self.external_acceleration(particles, i);
+            neighbors.for_each(particles, i, |_, p1| {
+                a += self.pair_acceleration(p0, p1);
+            });
+            a
+        });
+    }
+
+    /// Newtonian accelerations from `external_force` and the `source_force` of every other
+    /// particle in every field, with distant clusters merged by the tree.
+    fn far_field_accelerations(
+        &self,
+        theta: f64,
+        tree: &mut BarnesHutTree<P::Vector>,
+        particles: &[Particle<P::Props, P::Vector>],
+        accelerations: &mut [P::Vector],
+    ) {
+        // 力を足し上げてから最後に質量で割る．
+        for_each_output(accelerations, |i| {
+            let mut rng = SimRng::for_stream(self.seed, self.index, i as u64);
+            self.params
+                .external_force(&particles[i], self.delta_time, &mut rng)
+        });
+        let mut field_forces = vec![P::Vector::default(); particles.len()];
+        for field in 0..self.params.source_fields() {
+            tree.rebuild(
+                particles
+                    .iter()
+                    .map(|p| (p.position, self.params.source_strength(p, field))),
+            );
+            let tree = &*tree;
+            for_each_output(&mut field_forces, |i| {
+                let p = &particles[i];
+                let mut force = P::Vector::default();
+                tree.for_each_source(p.position, theta, i, |position, strength| {
+                    force += self.params.source_force(p, position, strength, field);
+                });
+                force
+            });
+            for (a, f) in accelerations.iter_mut().zip(&field_forces) {
+                *a += *f;
+            }
+        }
+        for (a, p) in accelerations.iter_mut().zip(particles) {
+            *a = *a / p.mass;
+        }
+    }
+
+    /// Evaluates every unordered pair once and gives both particles the opposite internal force,
+    /// each divided by its own mass.
+    fn symmetric_accelerations(
+        &self,
+        neighbors: &Neighborhood<P::Vector>,
+        particles: &[Particle<P::Props, P::Vector>],
+        accelerations: &mut [P::Vector],
+    ) {
+        // 組の力は添字の小さい側でまとめて評価する．
+        let mut outgoing = vec![Vec::new(); particles.len()];
+        for_each_output(&mut outgoing, |i| {
+            let p0 = &particles[i];
+            let mut forces = Vec::new();
+            neighbors.for_each(particles, i, |j, p1| {
+                if let Some(force) = (i < j).then(|| self.pair_force(p0, p1)).flatten() {
+                    forces.push((j, force));
+                }
+            });
+            forces
+        });
+
+        // 受け取る側では相手の添字の順に並べ，スレッド数によらず同じ順で足す．
+        let mut start = vec![0; particles.len() + 1];
+        for &(j, _) in outgoing.iter().flatten() {
+            start[j + 1] += 1;
+        }
+        for i in 0..particles.len() {
+            start[i + 1] += start[i];
+        }
+        let mut incoming = vec![P::Vector::default(); start[particles.len()]];
+        let mut next = start.clone();
+        for &(j, force) in outgoing.iter().flatten() {
+            incoming[next[j]] = force;
+            next[j] += 1;
+        }
+
+        // 作用反作用で運動量を保つように，同じ力をそれぞれの質量で割る．
+        for_each_output(accelerations, |i| {
+            let p = &particles[i];
+            let mut a = self.external_acceleration(particles, i);
+            for &force in &incoming[start[i]..start[i + 1]] {
+                a -= force / p.mass;
+            }
+            for &(_, force) in &outgoing[i] {
+                a += force / p.mass;
+            }
+            a
+        });
+    }
+}
+
+impl<P: ParticleSystemParameters> ParticleSystem<P> {
+    pub fn new(
+        params: P,
+        particles: impl IntoIterator<Item = Particle<P::Props, P::Vector>>,
+    ) -> ParticleSystem<P> {
+        let particles0 = Vec::from_iter(particles);
+        let mut slots = ParticleSlots::default();
+        for _ in &particles0 {
+            slots.insert();
+        }
+        ParticleSystem {
+            particles0,
+            particles1: Vec::new(),
+            slots,
+            params,
+            grid: SpatialGrid::default(),
+            tree: BarnesHutTree::default(),
+            integrator: SymplecticEuler,
+            boundary: Boundary::default(),
+            collisions: None,
+            thermostat: None,
+            adaptive_step: None,
+            opening_angle: None,
+            bonds: Vec::new(),
+            broken_bonds: Vec::new(),
+            constraints: Vec::new(),
+            solver: ConstraintSolver::default(),
+            seed: 0,
+            evaluations: 0,
+        }
+    }
+}
+
+impl<P: ParticleSystemParameters, I: Integrator> ParticleSystem<P, I> {
+    pub fn with_integrator<J: Integrator>(self, integrator: J) -> ParticleSystem<P, J> {
+        ParticleSystem {
+            particles0: self.particles0,
+            particles1: self.particles1,
+            slots: self.slots,
+            params: self.params,
+            grid: self.grid,
+            tree: self.tree,
+            integrator,
+            boundary: self.boundary,
+            collisions: self.collisions,
+            thermostat: self.thermostat,
+            adaptive_step: self.adaptive_step,
+            opening_angle: self.opening_angle,
+            bonds: self.bonds,
+            broken_bonds: self.broken_bonds,
+            constraints: self.constraints,
+            solver: self.solver,
+            seed: self.seed,
+            evaluations: self.evaluations,
+        }
+    }
+
+    /// Seeds the random numbers handed to `external_force`. Runs with the same seed, particles and
+    /// inputs are identical.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.evaluations = 0;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary<P::Vector>) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn with_collisions(mut self, collisions: Collisions) -> Self {
+        self.collisions = Some(collisions);
+        self
+    }
+
+    /// Couples the particles to a heat bath after every integration step, before collisions and
+    /// constraints are resolved.
+    pub fn with_thermostat(mut self, thermostat: Thermostat) -> Self {
+        self.thermostat = Some(thermostat);
+        self
+    }
+
+    /// The thermostat can be changed between updates, for example to heat or cool the system.
+    pub fn thermostat_mut(&mut self) -> Option<&mut Thermostat> {
+        self.thermostat.as_mut()
+    }
+
+    /// Lets `update` split its time step into substeps chosen one at a time by `adaptive_step`.
+    pub fn with_adaptive_step(mut self, adaptive_step: AdaptiveStep) -> Self {
+        self.adaptive_step = Some(adaptive_step);
+        self
+    }
+
+    /// Replaces the pairwise evaluation by the long-range field of `source_strength` and
+    /// `source_force`, summed over a Barnes–Hut tree in O(n log n). Clusters whose size is below
+    /// `theta` times their distance count as a single source; `theta = 0` is the exact direct sum.
+    /// `internal_force` is not used, and the sum does not wrap around periodic boundaries.
+    pub fn with_barnes_hut(mut self, theta: f64) -> Self {
+        self.opening_angle = Some(theta);
+        self
+    }
+
+    /// Human readable snapshot. Floats are written with enough digits to restore them exactly.
+    pub fn to_json(&self) -> serde_json::Result<String>
+    where
+        Self: Serialize,
+    {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        serde_json::from_str(json)
+    }
+
+    /// Compact binary snapshot.
+    pub fn to_bytes(&self) -> bincode::Result<Vec<u8>>
+    where
+        Self: Serialize,
+    {
+        bincode::serialize(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> bincode::Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        bincode::deserialize(bytes)
+    }
+
+    /// Links the two particles of `bond` until it breaks or either particle is gone.
+    pub fn add_bond(&mut self, bond: Bond) {
+        self.bonds.push(bond);
+    }
+
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+
+    /// Bonds that broke since the last call, in the order they broke.
+    pub fn take_broken_bonds(&mut self) -> Vec<Bond> {
+        std::mem::take(&mut self.broken_bonds)
+    }
+
+    /// Settings for projecting the constraints added with `add_constraint`.
+    pub fn with_constraint_solver(mut self, solver: ConstraintSolver) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Enforces `constraint` at the end of every update until any of its particles is gone.
+    pub fn add_constraint(&mut self, constraint: Constraint<P::Vector>) {
+        self.constraints.push(constraint);
+    }
+
+    pub fn constraints(&self) -> &[Constraint<P::Vector>] {
+        &self.constraints
+    }
+
+    pub fn params(&self) -> &P {
+        &self.params
+    }
+
+    /// Parameters can be changed between updates, for example to toggle force fields.
+    pub fn params_mut(&mut self) -> &mut P {
+        &mut self.params
+    }
+
+    pub fn particles(&self) -> &[Particle<P::Props, P::Vector>] {
+        &self.particles0
+    }
+
+    /// Ids of the particles in the same order as `particles`.
+    pub fn ids(&self) -> &[ParticleId] {
+        self.slots.ids()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ParticleId, &Particle<P::Props, P::Vector>)> {
+        self.slots.ids().iter().copied().zip(&self.particles0)
+    }
+
+    pub fn get(&self, id: ParticleId) -> Option<&Particle<P::Props, P::Vector>> {
+        self.slots.index_of(id).map(|i| &self.particles0[i])
+    }
+
+    pub fn get_mut(&mut self, id: ParticleId) -> Option<&mut Particle<P::Props, P::Vector>> {
+        self.slots.index_of(id).map(|i| &mut self.particles0[i])
+    }
+
+    pub fn spawn(&mut self, particle: Particle<P::Props, P::Vector>) -> ParticleId {
+        // 前の状態が揃っていれば，補間で飛ばないように同じ位置から始める．
+        if self.particles1.len() == self.particles0.len() {
+            self.particles1.push(particle.clone());
+        }
+        self.particles0.push(particle);
+        self.slots.insert()
+    }
+
+    pub fn despawn(&mut self, id: ParticleId) -> Option<Particle<P::Props, P::Vector>> {
+        let i = self.slots.swap_remove(id)?;
+        if self.particles1.len() == self.particles0.len() {
+            self.particles1.swap_remove(i);
+        }
+        Some(self.particles0.swap_remove(i))
+    }
+
+    /// Positions blended between the state before the last `update` (`alpha = 0`) and the current one (`alpha = 1`).
+    pub fn interpolated_positions(&self, alpha: f64) -> impl Iterator<Item = P::Vector> + '_ {
+        let alpha = ScalarOf::<P>::from_f64(alpha);
+        let previous = (self.particles1.len() == self.particles0.len()).then_some(&self.particles1);
+        let boundary = &self.boundary;
+        self.particles0
+            .iter()
+            .enumerate()
+            .map(move |(i, p)| match previous {
+                Some(previous) => {
+                    let from = previous[i].position;
+                    boundary.wrap(from + boundary.separation(from, p.position) * alpha)
+                }
+                None => p.position,
+            })
+    }
+
+    /// Sum of `pair_potential` over every pair within the cutoff, or `None` if the parameters do not
+    /// define one. Summed in `f64` whatever the scalar of the particles.
+    pub fn potential_energy(&self) -> Option<f64> {
+        self.potential_shares().map(|shares| shares.iter().sum())
+    }
+
+    /// Potential energy attributed to each particle, half of every pair it takes part in.
+    fn potential_shares(&self) -> Option<Vec<f64>> {
+        let particles = &self.particles0;
+        let mut grid = SpatialGrid::default();
+        let neighbors = Neighborhood::new(
+            self.params.cutoff_radius(),
+            &self.boundary,
+            &mut grid,
+            particles,
+        );
+
+        let mut shares = vec![0.0; particles.len()];
+        let mut defined = true;
+        for (i, p0) in particles.iter().enumerate() {
+            neighbors.for_each(particles, i, |j, p1| {
+                if j <= i || !defined {
+                    return;
+                }
+                let p1 = nearest_image(&self.boundary, p0, p1);
+                match self.params.pair_potential(p0, &p1) {
+                    Some(u) => {
+                        let u = u.to_f64();
+                        shares[i] += u / 2.0;
+                        shares[j] += u / 2.0;
+                    }
+                    None => defined = false,
+                }
+            });
+        }
+        defined.then_some(shares)
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics<P::Vector> {
+        let particles: Vec<_> = self.particles0.iter().collect();
+        let potential = self.potential_energy();
+        Diagnostics::measure(&particles, potential)
+    }
+
+    /// Diagnostics for each group of particles sharing the same `key` of their props.
+    pub fn diagnostics_by<K: Ord>(
+        &self,
+        key: impl Fn(&P::Props) -> K,
+    ) -> BTreeMap<K, Diagnostics<P::Vector>> {
+        let shares = self.potential_shares();
+        let mut groups = BTreeMap::<K, (Vec<_>, f64)>::new();
+        for (i, p) in self.particles0.iter().enumerate() {
+            let group = groups.entry(key(&p.props)).or_default();
+            group.0.push(p);
+            group.1 += shares.as_ref().map_or(0.0, |s| s[i]);
+        }
+        groups
+            .into_iter()
+            .map(|(k, (particles, potential))| {
+                let potential = shares.is_some().then_some(potential);
+                (k, Diagnostics::measure(&particles, potential))
+            })
+            .collect()
+    }
+
+    /// Advances the system by `delta_time` and returns the number of substeps taken, which is 1
+    /// unless `with_adaptive_step` is set.
+    pub fn update(&mut self, delta_time: ScalarOf<P>) -> u32 {
+        let Some(adaptive_step) = self.adaptive_step else {
+            self.step(delta_time, None);
+            return 1;
+        };
+
+        let start = self.particles0.clone();
+        let start_ids = self.slots.ids().to_vec();
+        let mut remaining = delta_time;
+        let mut substeps = 0;
+        while remaining > ScalarOf::<P>::ZERO {
+            let accelerations = self.accelerations(remaining);
+            let substep =
+                adaptive_step.substep(&self.particles0, &accelerations, remaining, substeps);
+            // 力は残り時間を刻みとして評価されたので，刻みがそれと同じときだけ使い回す．
+            let first = (substep == remaining).then_some(accelerations);
+            self.step(substep, first);
+            remaining -= substep;
+            substeps += 1;
+        }
+
+        // 補間は更新全体にわたるように，途中で消えた粒子を除いた更新前の状態を残す．
+        let mut ids = self.slots.ids().iter().peekable();
+        self.particles1 = start
+            .into_iter()
+            .zip(start_ids)
+            .filter(|(_, id)| ids.next_if_eq(&id).is_some())
+            .map(|(p, _)| p)
+            .collect();
+        substeps
+    }
+
+    /// Accelerations of the current particles, for choosing the next substep. The step is not
+    /// known yet, so forces see the time left in the update as their `delta_time`, and `update`
+    /// only hands them to the integrator when the substep turns out to be all of that time.
+    fn accelerations(&mut self, delta_time: ScalarOf<P>) -> Vec<P::Vector> {
+        let bonds = self.bond_indices();
+        let evaluation = Evaluation {
+            params: &self.params,
+            boundary: &self.boundary,
+            opening_angle: self.opening_angle,
+            bonds: &bonds,
+            delta_time,
+            seed: self.seed,
+            // 積分の最初の評価と同じ乱数列なので，外力も同じになる．
+            index: self.evaluations,
+        };
+        let mut accelerations = vec![P::Vector::default(); self.particles0.len()];
+        evaluation.accelerations(
+            &mut self.grid,
+            &mut self.tree,
+            &self.particles0,
+            &mut accelerations,
+        );
+        accelerations
+    }
+
+    /// Drops the bonds to removed particles and pairs the others with the indices of their ends.
+    fn bond_indices(&mut self) -> Vec<(usize, usize, Bond)> {
+        let slots = &self.slots;
+        self.bonds
+            .retain(|bond| slots.index_of(bond.a).is_some() && slots.index_of(bond.b).is_some());
+        self.bonds
+            .iter()
+            .map(|&bond| {
+                let index = |id| slots.index_of(id).unwrap();
+                (index(bond.a), index(bond.b), bond)
+            })
+            .collect()
+    }
+
+    /// Takes one step of `delta_time`. `first` stands in for the first evaluation of the
+    /// integrator when the accelerations of the current particles were already evaluated with
+    /// this `delta_time`.
+    fn step(&mut self, delta_time: ScalarOf<P>, mut first: Option<Vec<P::Vector>>) {
+        let bonds = self.bond_indices();
+        let params = &self.params;
+        let boundary = &self.boundary;
+        let grid = &mut self.grid;
+        let tree = &mut self.tree;
+        let opening_angle = self.opening_angle;
+        let target = &mut self.particles1;
+        let seed = self.seed;
+        let evaluations = &mut self.evaluations;
+
+        let slots = &self.slots;
+
+        self.constraints
+            .retain(|c| c.ids().all(|id| slots.index_of(id).is_some()));
+        let constraints: Vec<_> = self
+            .constraints
+            .iter()
+            .map(|&c| {
+                let mut ids = c.ids().map(|id| slots.index_of(id).unwrap());
+                let a = ids.next().unwrap();
+                (c, [a, ids.next().unwrap_or(a)])
+            })
+            .collect();
+
+        target.clone_from(&self.particles0);
+        self.integrator
+            .step(target, delta_time, |particles, accelerations| {
+                if let Some(first) = first.take() {
+                    accelerations.copy_from_slice(&first);
+                } else {
+                    let evaluation = Evaluation {
+                        params,
+                        boundary,
+                        opening_angle,
+                        bonds: &bonds,
+                        delta_time,
+                        seed,
+                        index: *evaluations,
+                    };
+                    evaluation.accelerations(grid, tree, particles, accelerations);
+                }
+                *evaluations += 1;
+            });
+
+        if let Some(thermostat) = &self.thermostat {
+            // 粒子の添字と重ならない系列を使う．
+            let mut rng = SimRng::for_stream(seed, self.evaluations, u64::MAX);
+            thermostat.apply(target, delta_time, &mut rng);
+        }
+
+        if let Some(collisions) = &self.collisions {
+            collisions.resolve(target, boundary, grid);
+        }
+
+        self.solver
+            .solve(&constraints, target, boundary, delta_time);
+
+        let keep: Vec<_> = target
+            .iter_mut()
+            .map(|p| {
+                p.age += delta_time;
+                boundary.apply(p) && !p.is_expired()
+            })
+            .collect();
+        if keep.contains(&false) {
+            // 補間用に前の状態からも同じ粒子を取り除く．
+            let mut k = keep.iter();
+            target.retain(|_| *k.next().unwrap());
+            let mut k = keep.iter();
+            self.particles0.retain(|_| *k.next().unwrap());
+            self.slots.retain(&keep);
+        }
+
+        swap(&mut self.particles0, &mut self.particles1);
+
+        let (particles, slots) = (&self.particles0, &self.slots);
+        let broken = &mut self.broken_bonds;
+        self.bonds.retain(|bond| {
+            let (Some(a), Some(b)) = (slots.index_of(bond.a), slots.index_of(bond.b)) else {
+                return false;
+            };
+            let is_broken = bond.is_broken(boundary, &particles[a], &particles[b]);
+            if is_broken {
+                broken.push(*bond);
+            }
+            !is_broken
+        });
+
+        self.params.advance(delta_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::boundary::Domain;
+    use crate::integrator::{RungeKutta4, VelocityVerlet};
+    use crate::particle_param::rnd_vec;
+    use crate::test_util::scalar_tests;
+    use crate::vector::Vector3;
+
+    fn v(x: f64, y: f64) -> Vector2<f64> {
+        Vector2 { x, y }
+    }
+
+    fn short_range(distance: f64) -> f64 {
+        if distance < 20.0 {
+            distance - 20.0
+        } else if distance < 40.0 {
+            0.5 * (40.0 - distance)
+        } else {
+            0.0
+        }
+    }
+
+    /// `short_range` in any dimension and scalar.
+    struct ShortRange<V = Vector2<f64>> {
+        cutoff: Option<f64>,
+        vector: PhantomData<V>,
+    }
+
+    impl<V> ShortRange<V> {
+        fn new(cutoff: Option<f64>) -> ShortRange<V> {
+            ShortRange {
+                cutoff,
+                vector: PhantomData,
+            }
+        }
+    }
+
+    impl<V: SimVector> ParticleSystemParameters for ShortRange<V> {
+        type Props = ();
+
+        type Vector = V;
+
+        fn external_force(&self, _: &Particle<(), V>, _: V::Scalar, _: &mut SimRng) -> V {
+            V::default()
+        }
+
+        fn internal_force(
+            &self,
+            p_target: &Particle<(), V>,
+            p_other: &Particle<(), V>,
+            _: V::Scalar,
+        ) -> V::Scalar {
+            let distance = (p_other.position - p_target.position).length();
+            V::Scalar::from_f64(short_range(distance.to_f64()))
+        }
+
+        fn cutoff_radius(&self) -> Option<V::Scalar> {
+            self.cutoff.map(V::Scalar::from_f64)
+        }
+    }
+
+    fn random_particles(count: usize, seed: u64, extent: f64) -> Vec<Particle<()>> {
+        scattered(count, seed, extent)
+    }
+
+    /// Same draws as `random_particles`, in any dimension and scalar.
+    fn scattered<V: SimVector>(count: usize, seed: u64, extent: f64) -> Vec<Particle<(), V>> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut draw = |range| V::Scalar::from_f64(rng.gen_range(range));
+        (0..count)
+            .map(|_| Particle {
+                props: (),
+                mass: draw(0.5..2.0),
+                position: V::from_fn(|_| draw(-extent..extent)),
+                velocity: V::from_fn(|_| draw(-5.0..5.0)),
+                radius: None,
+                age: V::Scalar::ZERO,
+                lifetime: None,
+            })
+            .collect()
+    }
+
+    /// Largest difference in position or velocity between the grid and every pair after 20 updates.
+    fn grid_error<V: SimVector>(particles: Vec<Particle<(), V>>, boundary: Boundary<V>) -> f64 {
+        let mut brute =
+            ParticleSystem::new(ShortRange::new(None), particles.clone()).with_boundary(boundary);
+        let mut grid =
+            ParticleSystem::new(ShortRange::new(Some(40.0)), particles).with_boundary(boundary);
+
+        for _ in 0..20 {
+            brute.update(V::Scalar::from_f64(0.05));
+            grid.update(V::Scalar::from_f64(0.05));
+        }
+
+        brute
+            .particles()
+            .iter()
+            .zip(grid.particles())
+            .map(|(b, g)| {
+                let position = boundary.separation(b.position, g.position).length();
+                let velocity = (b.velocity - g.velocity).length();
+                position.max(velocity).to_f64()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_grid_matches_brute_force() {
+        let particles = random_particles(600, 1, 300.0);
+        assert!(grid_error(particles, Boundary::Unbounded) < 1e-9);
+
+        let particles = scattered::<Vector2<f32>>(600, 1, 300.0);
+        assert!(grid_error(particles, Boundary::Unbounded) < 1e-2);
+    }
+
+    #[test]
+    fn test_periodic_grid_matches_brute_force() {
+        let particles = random_particles(300, 3, 100.0);
+        assert!(grid_error(particles, Boundary::Periodic(domain())) < 1e-9);
+
+        let particles = scattered::<Vector2<f32>>(300, 3, 100.0);
+        assert!(grid_error(particles, Boundary::Periodic(domain())) < 1e-2);
+    }
+
+    #[test]
+    fn test_grid_matches_brute_force_in_three_dimensions() {
+        let particles = scattered::<Vector3<f64>>(300, 13, 100.0);
+        assert!(grid_error(particles, Boundary::Periodic(domain())) < 1e-9);
+    }
+
+    struct Spring<S = f64>(PhantomData<S>);
+
+    impl<S: Scalar> ParticleSystemParameters for Spring<S> {
+        type Props = ();
+
+        type Vector = Vector2<S>;
+
+        fn external_force(&self, _: &Particle<(), Vector2<S>>, _: S, _: &mut SimRng) -> Vector2<S> {
+            Vector2::default()
+        }
+
+        fn internal_force(
+            &self,
+            p_target: &Particle<(), Vector2<S>>,
+            p_other: &Particle<(), Vector2<S>>,
+            _: S,
+        ) -> S {
+            (p_other.position - p_target.position).length() - S::ONE
+        }
+    }
+
+    fn oscillator_error<S: Scalar>(system: &mut ParticleSystem<Spring<S>, impl Integrator>) -> f64 {
+        let delta_time = 0.01;
+        let steps = 1000;
+        for _ in 0..steps {
+            system.update(S::from_f64(delta_time));
+        }
+        let [p0, p1] = system.particles() else {
+            unreachable!()
+        };
+        // 各粒子に k(d - 1) の力がかかるので，距離は角振動数 sqrt(2) で振動する．
+        let expected = 1.0 + 0.5 * (2.0f64.sqrt() * delta_time * steps as f64).cos();
+        ((p1.position - p0.position).length().to_f64() - expected).abs()
+    }
+
+    fn oscillator<S: Scalar>() -> ParticleSystem<Spring<S>> {
+        let particle = |x| Particle {
+            props: (),
+            mass: S::ONE,
+            position: Vector2 {
+                x: S::from_f64(x),
+                y: S::ZERO,
+            },
+            velocity: Vector2::default(),
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        };
+        ParticleSystem::new(Spring(PhantomData), [particle(-0.75), particle(0.75)])
+    }
+
+    #[test]
+    fn test_integrators_track_harmonic_oscillator() {
+        let euler = oscillator_error(&mut oscillator::<f64>());
+        let verlet = oscillator_error(&mut oscillator::<f64>().with_integrator(VelocityVerlet));
+        let rk4 = oscillator_error(&mut oscillator::<f64>().with_integrator(RungeKutta4));
+
+        assert!(euler < 1e-2);
+        assert!(verlet < 1e-3);
+        assert!(rk4 < 1e-7);
+        assert!(rk4 < verlet && verlet < euler);
+
+        // f32 では丸め誤差の分だけ RK4 の精度が落ちる．
+        let euler = oscillator_error(&mut oscillator::<f32>());
+        let verlet = oscillator_error(&mut oscillator::<f32>().with_integrator(VelocityVerlet));
+        let rk4 = oscillator_error(&mut oscillator::<f32>().with_integrator(RungeKutta4));
+
+        assert!(euler < 1e-2);
+        assert!(verlet < 1e-3);
+        assert!(rk4 < 1e-6);
+        assert!(rk4 < verlet && verlet < euler);
+    }
+
+    #[test]
+    fn test_adaptive_step_tames_long_steps() {
+        // 刻み 2 は角振動数 sqrt(2) のシンプレクティック Euler には長すぎて発散する．
+        let separation = |system: &ParticleSystem<Spring>| {
+            let [p0, p1] = system.particles() else {
+                unreachable!()
+            };
+            (p1.position - p0.position).length()
+        };
+
+        let mut fixed = oscillator::<f64>();
+        let mut adaptive = oscillator::<f64>().with_adaptive_step(AdaptiveStep::new(0.02));
+        for _ in 0..10 {
+            assert_eq!(fixed.update(2.0), 1);
+            let before: Vec<_> = adaptive.particles().iter().map(|p| p.position.x).collect();
+            let substeps = adaptive.update(2.0);
+            assert!((2..64).contains(&substeps), "{substeps}");
+            // 補間の始点は最後の部分刻みではなく更新の前．
+            assert!(adaptive.interpolated_positions(0.0).map(|p| p.x).eq(before));
+            // 距離は 0.5 から 1.5 の間で振動する．
+            assert!((separation(&adaptive) - 1.0).abs() < 0.55);
+        }
+
+        assert!(separation(&fixed) > 100.0);
+    }
+
+    /// `Spring` that counts the particles its external force is evaluated on.
+    #[derive(Default)]
+    struct Counted(std::sync::atomic::AtomicUsize);
+
+    impl ParticleSystemParameters for Counted {
+        type Props = ();
+
+        type Vector = Vector2<f64>;
+
+        fn external_force(&self, _: &Particle<()>, _: f64, _: &mut SimRng) -> Vector2<f64> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Vector2::default()
+        }
+
+        fn internal_force(&self, p_target: &Particle<()>, p_other: &Particle<()>, _: f64) -> f64 {
+            Spring(PhantomData).internal_force(p_target, p_other, 0.0)
+        }
+    }
+
+    #[test]
+    fn test_adaptive_substeps_reuse_only_a_matching_probe() {
+        let particles = oscillator::<f64>().particles().to_vec();
+        let mut system = ParticleSystem::new(Counted::default(), particles)
+            .with_adaptive_step(AdaptiveStep::new(0.02));
+        let mut substeps = 0;
+        for _ in 0..10 {
+            substeps += system.update(2.0);
+        }
+
+        // シンプレクティック Euler は 1 刻みに 1 回評価し，刻みを決める評価がそれぞれに加わる．
+        // 残り時間をすべて進む各更新の最後の刻みだけは，その評価を使い回す．
+        let evaluations = system.params().0.load(std::sync::atomic::Ordering::Relaxed);
+        assert!(substeps > 10);
+        assert_eq!(evaluations, 2 * (2 * substeps as usize - 10));
+    }
+
+    fn domain<V: SimVector>() -> Domain<V> {
+        Domain {
+            min: V::from_fn(|_| V::Scalar::from_f64(-100.0)),
+            max: V::from_fn(|_| V::Scalar::from_f64(100.0)),
+        }
+    }
+
+    fn assert_inside<V: SimVector>(system: &ParticleSystem<ShortRange<V>>) {
+        let Domain { min, max } = domain::<V>();
+        for p in system.particles() {
+            for i in 0..V::LEN {
+                assert!(min.get(i) <= p.position.get(i) && p.position.get(i) <= max.get(i));
+            }
+        }
+    }
+
+    fn assert_periodic_boundary_keeps_particles_inside<V: SimVector>() {
+        let mut system =
+            ParticleSystem::new(ShortRange::new(Some(40.0)), scattered::<V>(300, 2, 100.0))
+                .with_boundary(Boundary::Periodic(domain()));
+        for _ in 0..50 {
+            system.update(V::Scalar::from_f64(0.5));
+            assert_inside(&system);
+        }
+        assert_eq!(system.particles().len(), 300);
+    }
+
+    #[test]
+    fn test_periodic_boundary_keeps_particles_inside() {
+        assert_periodic_boundary_keeps_particles_inside::<Vector2<f64>>();
+        assert_periodic_boundary_keeps_particles_inside::<Vector2<f32>>();
+        assert_periodic_boundary_keeps_particles_inside::<Vector3<f32>>();
+    }
+
+    #[test]
+    fn test_periodic_boundary_uses_minimum_image() {
+        let particle = |x| Particle {
+            props: (),
+            mass: 1.0,
+            position: v(x, 0.0),
+            velocity: v(0.0, 0.0),
+            radius: None,
+            age: 0.0,
+            lifetime: None,
+        };
+        let mut across =
+            ParticleSystem::new(Spring(PhantomData), [particle(-99.5), particle(99.5)])
+                .with_boundary(Boundary::Periodic(domain()));
+        across.update(0.01);
+
+        let [p0, p1] = across.particles() else {
+            unreachable!()
+        };
+        // 像の間の距離は 1 なので力はかからない．
+        assert_eq!(p0.velocity.x, 0.0);
+        assert_eq!(p1.velocity.x, 0.0);
+    }
+
+    #[test]
+    fn test_reflective_boundary_keeps_particles_inside() {
+        for restitution in [1.0, 0.5] {
+            let mut system =
+                ParticleSystem::new(ShortRange::new(Some(40.0)), random_particles(300, 4, 100.0))
+                    .with_boundary(Boundary::Reflective {
+                        domain: domain(),
+                        restitution,
+                    });
+            for _ in 0..50 {
+                system.update(0.5);
+                assert_inside(&system);
+            }
+            assert_eq!(system.particles().len(), 300);
+        }
+    }
+
+    #[test]
+    fn test_open_boundary_removes_escaped_particles() {
+        let mut system =
+            ParticleSystem::new(ShortRange::new(Some(40.0)), random_particles(300, 5, 100.0))
+                .with_boundary(Boundary::Open(domain()));
+        for _ in 0..50 {
+            system.update(0.5);
+            assert_inside(&system);
+        }
+        assert!(system.particles().len() < 300);
+    }
+
+    fn test_ids_survive_updates_and_despawns<S: Scalar>() {
+        let mut system = ParticleSystem::new(
+            ShortRange::new(Some(40.0)),
+            scattered::<Vector2<S>>(50, 6, 100.0),
+        );
+        let ids = system.ids().to_vec();
+
+        let removed = system.despawn(ids[10]).unwrap();
+        assert!(system.get(ids[10]).is_none());
+        assert!(system.despawn(ids[10]).is_none());
+
+        let spawned = system.spawn(removed);
+        assert_ne!(spawned, ids[10]);
+        assert!(system.get(ids[10]).is_none());
+
+        let mass = S::from_f64(3.0);
+        system.get_mut(spawned).unwrap().mass = mass;
+        for _ in 0..5 {
+            system.update(S::from_f64(0.1));
+        }
+        assert_eq!(system.get(spawned).unwrap().mass, mass);
+        assert_eq!(system.particles().len(), 50);
+        for (id, p) in system.iter() {
+            assert!(std::ptr::eq(system.get(id).unwrap(), p));
+        }
+        for &id in ids.iter().filter(|&&id| id != ids[10]) {
+            assert!(system.get(id).is_some());
+        }
+    }
+
+    fn test_open_boundary_invalidates_ids<S: Scalar>() {
+        let mut system = ParticleSystem::new(
+            ShortRange::new(Some(40.0)),
+            scattered::<Vector2<S>>(300, 7, 100.0),
+        )
+        .with_boundary(Boundary::Open(domain()));
+        let ids = system.ids().to_vec();
+        for _ in 0..50 {
+            system.update(S::from_f64(0.5));
+        }
+
+        let alive = ids.iter().filter(|&&id| system.get(id).is_some()).count();
+        assert_eq!(alive, system.particles().len());
+        assert!(alive < 300);
+        for (id, p) in system.iter() {
+            assert!(domain().contains(p.position));
+            assert!(std::ptr::eq(system.get(id).unwrap(), p));
+        }
+    }
+
+    struct Vortex;
+
+    impl ParticleSystemParameters for Vortex {
+        type Props = ();
+
+        type Vector = Vector2<f64>;
+
+        fn external_force(&self, _: &Particle<()>, _: f64, _: &mut SimRng) -> Vector2<f64> {
+            v(0.0, 0.0)
+        }
+
+        fn internal_force_vector(
+            &self,
+            p_target: &Particle<()>,
+            p_other: &Particle<()>,
+            _: f64,
+        ) -> Vector2<f64> {
+            let delta = p_other.position - p_target.position;
+            v(-delta.y, delta.x)
+        }
+    }
+
+    struct Alignment;
+
+    impl ParticleSystemParameters for Alignment {
+        type Props = ();
+
+        type Vector = Vector2<f64>;
+
+        fn external_force(&self, _: &Particle<()>, _: f64, _: &mut SimRng) -> Vector2<f64> {
+            v(0.0, 0.0)
+        }
+
+        fn internal_force_vector(
+            &self,
+            p_target: &Particle<()>,
+            p_other: &Particle<()>,
+            _: f64,
+        ) -> Vector2<f64> {
+            (p_other.velocity - p_target.velocity) * 0.5
+        }
+    }
+
+    #[test]
+    fn test_vector_force_acts_tangentially() {
+        let particle = |x| Particle {
+            props: (),
+            mass: 1.0,
+            position: v(x, 0.0),
+            velocity: v(0.0, 0.0),
+            radius: None,
+            age: 0.0,
+            lifetime: None,
+        };
+        let mut system = ParticleSystem::new(Vortex, [particle(-1.0), particle(1.0)]);
+        system.update(0.01);
+
+        let [p0, p1] = system.particles() else {
+            unreachable!()
+        };
+        assert_eq!(p0.velocity.x, 0.0);
+        assert!(p0.velocity.y > 0.0);
+        assert!(p1.velocity.y < 0.0);
+    }
+
+    #[test]
+    fn test_velocity_dependent_force_aligns_particles() {
+        let particle = |x, vy| Particle {
+            props: (),
+            mass: 1.0,
+            position: v(x, 0.0),
+            velocity: v(0.0, vy),
+            radius: None,
+            age: 0.0,
+            lifetime: None,
+        };
+        let mut system = ParticleSystem::new(Alignment, [particle(-1.0, 1.0), particle(1.0, -1.0)]);
+        for _ in 0..100 {
+            system.update(0.05);
+        }
+
+        let [p0, p1] = system.particles() else {
+            unreachable!()
+        };
+        assert!((p0.velocity - p1.velocity).length() < 0.02);
+        assert!((p0.velocity + p1.velocity).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_diagnostics_by_kind_partition_the_total() {
+        let particles = random_particles(40, 8, 10.0)
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| Particle {
+                props: i % 3,
+                mass: p.mass,
+                position: p.position,
+                velocity: p.velocity,
+                radius: p.radius,
+                age: p.age,
+                lifetime: p.lifetime,
+            });
+        let params = crate::potential::PotentialParameters::new(
+            crate::potential::Yukawa {
+                strength: 1.0,
+                screening_length: 5.0,
+            },
+            None,
+        );
+        let system = ParticleSystem::new(params, particles);
+
+        let total = system.diagnostics();
+        let groups = system.diagnostics_by(|&kind| kind);
+        assert_eq!(groups.len(), 3);
+
+        let sum = |f: fn(&Diagnostics) -> f64| groups.values().map(f).sum::<f64>();
+        assert_eq!(sum(|d| d.count as f64), total.count as f64);
+        assert!((sum(|d| d.mass) - total.mass).abs() < 1e-9);
+        assert!((sum(|d| d.kinetic_energy) - total.kinetic_energy).abs() < 1e-9);
+        assert!((sum(|d| d.momentum.x) - total.momentum.x).abs() < 1e-9);
+        assert!(
+            (sum(|d| d.potential_energy.unwrap()) - total.potential_energy.unwrap()).abs() < 1e-9
+        );
+    }
+
+    struct Dragged {
+        symmetric: bool,
+    }
+
+    impl ParticleSystemParameters for Dragged {
+        type Props = ();
+
+        type Vector = Vector2<f64>;
+
+        fn external_force(&self, p: &Particle<()>, _: f64, _: &mut SimRng) -> Vector2<f64> {
+            v(0.0, -1.0) - p.velocity * 0.1
+        }
+
+        fn internal_force(&self, p_target: &Particle<()>, p_other: &Particle<()>, _: f64) -> f64 {
+            ShortRange::new(None).internal_force(p_target, p_other, 0.0)
+        }
+
+        fn cutoff_radius(&self) -> Option<f64> {
+            Some(40.0)
+        }
+
+        fn symmetric(&self) -> bool {
+            self.symmetric
+        }
+    }
+
+    #[test]
+    fn test_symmetric_path_matches_pairwise() {
+        let particles = random_particles(400, 12, 100.0);
+        let system = |symmetric| {
+            ParticleSystem::new(Dragged { symmetric }, particles.clone())
+                .with_boundary(Boundary::Periodic(domain()))
+        };
+        let mut pairwise = system(false);
+        let mut symmetric = system(true);
+
+        for _ in 0..20 {
+            pairwise.update(0.05);
+            symmetric.update(0.05);
+        }
+
+        for (a, b) in pairwise.particles().iter().zip(symmetric.particles()) {
+            assert!((a.position - b.position).length() < 1e-9);
+            assert!((a.velocity - b.velocity).length() < 1e-9);
+        }
+    }
+
+    /// `short_range` alone, so that nothing but the pairs exchanges momentum.
+    struct Mutual {
+        symmetric: bool,
+    }
+
+    impl ParticleSystemParameters for Mutual {
+        type Props = ();
+
+        type Vector = Vector2<f64>;
+
+        fn external_force(&self, _: &Particle<()>, _: f64, _: &mut SimRng) -> Vector2<f64> {
+            Vector2::default()
+        }
+
+        fn internal_force(&self, p_target: &Particle<()>, p_other: &Particle<()>, _: f64) -> f64 {
+            ShortRange::new(None).internal_force(p_target, p_other, 0.0)
+        }
+
+        fn cutoff_radius(&self) -> Option<f64> {
+            Some(40.0)
+        }
+
+        fn symmetric(&self) -> bool {
+            self.symmetric
+        }
+    }
+
+    #[test]
+    fn test_pairs_conserve_momentum_with_mixed_masses() {
+        // 質量は 0.5 から 2 までばらつく．
+        let particles = random_particles(300, 13, 100.0);
+        for symmetric in [false, true] {
+            let mut system = ParticleSystem::new(Mutual { symmetric }, particles.clone())
+                .with_boundary(Boundary::Periodic(domain()));
+            let before = system.diagnostics().momentum;
+            for _ in 0..50 {
+                system.update(0.05);
+            }
+
+            let after = system.diagnostics().momentum;
+            assert!((after - before).length() < 1e-9 * before.length().max(1.0));
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Noisy<S = f64>(PhantomData<S>);
+
+    impl<S: Scalar> ParticleSystemParameters for Noisy<S> {
+        type Props = ();
+
+        type Vector = Vector2<S>;
+
+        fn external_force(
+            &self,
+            _: &Particle<(), Vector2<S>>,
+            _: S,
+            rng: &mut SimRng,
+        ) -> Vector2<S> {
+            rnd_vec(rng, -1.0..1.0)
+        }
+
+        fn internal_force(
+            &self,
+            p_target: &Particle<(), Vector2<S>>,
+            p_other: &Particle<(), Vector2<S>>,
+            _: S,
+        ) -> S {
+            ShortRange::new(None).internal_force(p_target, p_other, S::ZERO)
+        }
+
+        fn cutoff_radius(&self) -> Option<S> {
+            Some(S::from_f64(40.0))
+        }
+    }
+
+    fn positions<S: Scalar>(system: &ParticleSystem<Noisy<S>>) -> Vec<(f64, f64)> {
+        system
+            .particles()
+            .iter()
+            .map(|p| (p.position.x.to_f64(), p.position.y.to_f64()))
+            .collect()
+    }
+
+    #[test]
+    fn test_seed_reproduces_runs() {
+        let particles = random_particles(100, 9, 100.0);
+        let mut a = ParticleSystem::new(Noisy(PhantomData), particles.clone()).with_seed(42);
+        let mut b = ParticleSystem::new(Noisy(PhantomData), particles.clone()).with_seed(42);
+        let mut c = ParticleSystem::new(Noisy(PhantomData), particles).with_seed(43);
+
+        for _ in 0..10 {
+            a.update(0.1);
+        }
+        // 他のシステムの更新が乱数に影響しないことも確かめる．
+        for _ in 0..10 {
+            c.update(0.1);
+            b.update(0.1);
+        }
+
+        assert_eq!(positions(&a), positions(&b));
+        assert_ne!(positions(&a), positions(&c));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_thread_count_does_not_change_results() {
+        let run = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut system =
+                ParticleSystem::new(Noisy(PhantomData), random_particles(500, 11, 100.0))
+                    .with_seed(5);
+            pool.install(|| {
+                for _ in 0..10 {
+                    system.update(0.1);
+                }
+            });
+            positions(&system)
+        };
+
+        assert_eq!(run(1), run(4));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_thread_count_does_not_change_symmetric_results() {
+        let run = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut system = ParticleSystem::new(
+                Dragged { symmetric: true },
+                random_particles(500, 14, 100.0),
+            )
+            .with_boundary(Boundary::Periodic(domain()));
+            pool.install(|| {
+                for _ in 0..10 {
+                    system.update(0.1);
+                }
+            });
+            system
+                .particles()
+                .iter()
+                .map(|p| (p.position.x, p.position.y))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(1), run(4));
+    }
+
+    fn test_snapshots_resume_identically<S: Scalar>() {
+        let particles = scattered::<Vector2<S>>(100, 10, 100.0)
+            .into_iter()
+            .map(|p| Particle {
+                radius: Some(S::from_f64(2.0)),
+                ..p
+            });
+        let mut original = ParticleSystem::new(Noisy::<S>(PhantomData), particles)
+            .with_seed(7)
+            .with_boundary(Boundary::Periodic(domain()))
+            .with_collisions(Collisions {
+                restitution: 0.8,
+                friction: 0.1,
+            });
+        let delta_time = S::from_f64(0.1);
+        for _ in 0..10 {
+            original.update(delta_time);
+        }
+        let id = original.ids()[3];
+        original.despawn(id);
+
+        let json = original.to_json().unwrap();
+        let bytes = original.to_bytes().unwrap();
+        let mut from_json = ParticleSystem::<Noisy<S>>::from_json(&json).unwrap();
+        let mut from_bytes = ParticleSystem::<Noisy<S>>::from_bytes(&bytes).unwrap();
+        assert!(bytes.len() < json.len());
+
+        for _ in 0..10 {
+            original.update(delta_time);
+            from_json.update(delta_time);
+            from_bytes.update(delta_time);
+        }
+        for restored in [&from_json, &from_bytes] {
+            assert_eq!(positions(restored), positions(&original));
+            assert_eq!(restored.ids(), original.ids());
+            assert_eq!(restored.seed(), original.seed());
+        }
+    }
+
+    scalar_tests!(
+        test_ids_survive_updates_and_despawns,
+        test_open_boundary_invalidates_ids,
+        test_snapshots_resume_identically,
+    );
+}
@@ -0,0 +1,250 @@
+use std::marker::PhantomData;
+
+use crate::particle_system::{MaybeSync, Particle, ParticleSystemParameters};
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Pair interaction described by a potential energy `U(r)` of the separation, evaluated in `f64`
+/// whatever the scalar of the particles.
+pub trait PairPotential {
+    fn potential(&self, r: f64) -> f64;
+
+    /// `dU/dr`. Defaults to a central difference of `potential`.
+    fn derivative(&self, r: f64) -> f64 {
+        let h = 1e-6 * r.max(1e-3);
+        (self.potential(r + h) - self.potential(r - h)) / (2.0 * h)
+    }
+}
+
+/// `4ε((σ/r)¹² - (σ/r)⁶)`
+#[derive(Debug, Clone, Copy)]
+pub struct LennardJones {
+    pub epsilon: f64,
+    pub sigma: f64,
+}
+
+/// `D((1 - e^(-a(r - r_e)))² - 1)`, zero at infinity and `-D` at `r_e`.
+#[derive(Debug, Clone, Copy)]
+pub struct Morse {
+    pub depth: f64,
+    pub width: f64,
+    pub equilibrium: f64,
+}
+
+/// Screened Coulomb potential `g e^(-r/λ) / r`. Positive strength repels.
+#[derive(Debug, Clone, Copy)]
+pub struct Yukawa {
+    pub strength: f64,
+    pub screening_length: f64,
+}
+
+/// Purely repulsive `ε(σ/r)ⁿ`.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftSphere {
+    pub epsilon: f64,
+    pub sigma: f64,
+    pub exponent: i32,
+}
+
+impl PairPotential for LennardJones {
+    fn potential(&self, r: f64) -> f64 {
+        let s6 = (self.sigma / r).powi(6);
+        4.0 * self.epsilon * (s6 * s6 - s6)
+    }
+
+    fn derivative(&self, r: f64) -> f64 {
+        let s6 = (self.sigma / r).powi(6);
+        24.0 * self.epsilon * (s6 - 2.0 * s6 * s6) / r
+    }
+}
+
+impl PairPotential for Morse {
+    fn potential(&self, r: f64) -> f64 {
+        let e = (-self.width * (r - self.equilibrium)).exp();
+        self.depth * ((1.0 - e).powi(2) - 1.0)
+    }
+
+    fn derivative(&self, r: f64) -> f64 {
+        let e = (-self.width * (r - self.equilibrium)).exp();
+        2.0 * self.depth * self.width * e * (1.0 - e)
+    }
+}
+
+impl PairPotential for Yukawa {
+    fn potential(&self, r: f64) -> f64 {
+        self.strength * (-r / self.screening_length).exp() / r
+    }
+
+    fn derivative(&self, r: f64) -> f64 {
+        -self.potential(r) * (1.0 / r + 1.0 / self.screening_length)
+    }
+}
+
+impl PairPotential for SoftSphere {
+    fn potential(&self, r: f64) -> f64 {
+        self.epsilon * (self.sigma / r).powi(self.exponent)
+    }
+
+    fn derivative(&self, r: f64) -> f64 {
+        -(self.exponent as f64) * self.potential(r) / r
+    }
+}
+
+/// Parameters whose only interaction is a single pair potential applied between all particles.
+/// With a cutoff the potential is shifted to vanish there, so reported energies stay continuous.
+pub struct PotentialParameters<U, Props = (), V = Vector2<f64>> {
+    pub potential: U,
+    pub cutoff: Option<f64>,
+    _marker: PhantomData<fn() -> (Props, V)>,
+}
+
+impl<U: PairPotential, Props, V> PotentialParameters<U, Props, V> {
+    pub fn new(potential: U, cutoff: Option<f64>) -> PotentialParameters<U, Props, V> {
+        PotentialParameters {
+            potential,
+            cutoff,
+            _marker: PhantomData,
+        }
+    }
+
+    fn within_cutoff(&self, r: f64) -> bool {
+        self.cutoff.is_none_or(|c| r < c)
+    }
+}
+
+impl<U, Props, V> ParticleSystemParameters for PotentialParameters<U, Props, V>
+where
+    U: PairPotential + MaybeSync,
+    Props: Clone + MaybeSync,
+    V: SimVector,
+{
+    type Props = Props;
+    type Vector = V;
+
+    fn external_force(&self, _: &Particle<Props, V>, _: V::Scalar, _: &mut SimRng) -> V {
+        V::default()
+    }
+
+    fn internal_force(
+        &self,
+        p_target: &Particle<Props, V>,
+        p_other: &Particle<Props, V>,
+        _: V::Scalar,
+    ) -> V::Scalar {
+        let r = (p_other.position - p_target.position).length().to_f64();
+        if self.within_cutoff(r) {
+            V::Scalar::from_f64(self.potential.derivative(r))
+        } else {
+            V::Scalar::ZERO
+        }
+    }
+
+    fn cutoff_radius(&self) -> Option<V::Scalar> {
+        self.cutoff.map(V::Scalar::from_f64)
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
+
+    fn pair_potential(
+        &self,
+        p_target: &Particle<Props, V>,
+        p_other: &Particle<Props, V>,
+    ) -> Option<V::Scalar> {
+        let r = (p_other.position - p_target.position).length().to_f64();
+        let u = match self.cutoff {
+            Some(c) if r < c => self.potential.potential(r) - self.potential.potential(c),
+            Some(_) => 0.0,
+            None => self.potential.potential(r),
+        };
+        Some(V::Scalar::from_f64(u))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::VelocityVerlet;
+    use crate::particle_system::ParticleSystem;
+    use crate::test_util::scalar_tests;
+
+    struct Numeric<U>(U);
+
+    impl<U: PairPotential> PairPotential for Numeric<U> {
+        fn potential(&self, r: f64) -> f64 {
+            self.0.potential(r)
+        }
+    }
+
+    fn assert_derivative_matches(potential: impl PairPotential + Copy) {
+        for r in [0.9, 1.1, 1.5, 2.5, 4.0] {
+            let analytic = potential.derivative(r);
+            let numeric = Numeric(potential).derivative(r);
+            assert!((analytic - numeric).abs() < 1e-6 * analytic.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn test_analytic_derivatives_match_numeric() {
+        assert_derivative_matches(LennardJones {
+            epsilon: 1.0,
+            sigma: 1.0,
+        });
+        assert_derivative_matches(Morse {
+            depth: 2.0,
+            width: 1.5,
+            equilibrium: 1.2,
+        });
+        assert_derivative_matches(Yukawa {
+            strength: 3.0,
+            screening_length: 0.7,
+        });
+        assert_derivative_matches(SoftSphere {
+            epsilon: 1.0,
+            sigma: 1.0,
+            exponent: 12,
+        });
+    }
+
+    fn test_lennard_jones_conserves_energy<S: Scalar>() {
+        // 質量が違っても力は F/m で効くので，エネルギーは保存する．
+        let particles = (0..16).map(|i| Particle {
+            props: (),
+            mass: S::from_f64(if i % 2 == 0 { 1.0 } else { 4.0 }),
+            position: Vector2 {
+                x: S::from_f64((i % 4) as f64 * 1.2),
+                y: S::from_f64((i / 4) as f64 * 1.2),
+            },
+            velocity: Vector2 {
+                x: S::from_f64(((i * 7) % 5) as f64 * 0.1 - 0.2),
+                y: S::from_f64(((i * 3) % 5) as f64 * 0.1 - 0.2),
+            },
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        });
+        let params = PotentialParameters::<_, (), Vector2<S>>::new(
+            LennardJones {
+                epsilon: 1.0,
+                sigma: 1.0,
+            },
+            None,
+        );
+        let mut system = ParticleSystem::new(params, particles).with_integrator(VelocityVerlet);
+
+        let energy = |system: &ParticleSystem<_, _>| {
+            let diagnostics = system.diagnostics();
+            diagnostics.kinetic_energy + diagnostics.potential_energy.unwrap()
+        };
+        let initial = energy(&system);
+        for _ in 0..2000 {
+            system.update(S::from_f64(0.001));
+        }
+
+        assert!((energy(&system) - initial).abs() < 1e-3 * initial.abs());
+    }
+
+    scalar_tests!(test_lennard_jones_conserves_energy,);
+}
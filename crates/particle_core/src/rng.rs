@@ -0,0 +1,60 @@
+use std::f64::consts::PI;
+
+use rand::{Error, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// SplitMix64. Unlike `SmallRng` it produces the same sequence on wasm32 and 64-bit targets,
+/// so a seed reproduces a run everywhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        SimRng { state: seed }
+    }
+
+    /// Independent generator for one `(stream, index)` pair under `seed`.
+    /// The system uses the force evaluation count and the particle index, so the random numbers a
+    /// particle sees do not depend on the order in which particles are processed.
+    pub fn for_stream(seed: u64, stream: u64, index: u64) -> SimRng {
+        let a = SimRng::new(seed).next_u64() ^ stream;
+        let b = SimRng::new(a).next_u64() ^ index;
+        SimRng::new(b)
+    }
+
+    /// Standard normal variate, by the Box–Muller transform.
+    pub fn gaussian(&mut self) -> f64 {
+        // 対数の引数が 0 にならないように (0, 1] から引く．
+        let u = 1.0 - self.gen::<f64>();
+        let v = self.gen::<f64>();
+        (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::particle_system::MaybeSync;
+
+/// Number type of positions, velocities, masses and time steps. `f64` by default; `f32` halves
+/// the memory of the particles.
+///
+/// Configuration such as spring constants or restitution stays `f64` and is converted with
+/// `from_f64` where it is used. `ParticleParam`, `ForceField`, `PairPotential` and `Diagnostics`
+/// compute through `f64` and emitters draw `f64` random numbers, so a fixed-point type alone does
+/// not make runs reproducible across platforms.
+pub trait Scalar:
+    Copy
+    + Default
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + Sum
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + MaybeSync
+{
+    const ZERO: Self;
+    const ONE: Self;
+    /// Distance from one to the next larger value.
+    const EPSILON: Self;
+
+    fn from_f64(value: f64) -> Self;
+
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+
+    fn sqrt(self) -> Self;
+
+    fn floor(self) -> Self;
+
+    fn round(self) -> Self;
+
+    /// Least non-negative remainder, as `f64::rem_euclid`.
+    fn rem_euclid(self, rhs: Self) -> Self;
+
+    fn min(self, other: Self) -> Self {
+        if other < self {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other > self {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn clamp(self, min: Self, max: Self) -> Self {
+        self.max(min).min(max)
+    }
+}
+
+macro_rules! impl_scalar {
+    ($($id:ident),+) => {$(
+        impl Scalar for $id {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const EPSILON: Self = $id::EPSILON;
+
+            fn from_f64(value: f64) -> Self {
+                value as $id
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn abs(self) -> Self {
+                $id::abs(self)
+            }
+
+            fn sqrt(self) -> Self {
+                $id::sqrt(self)
+            }
+
+            fn floor(self) -> Self {
+                $id::floor(self)
+            }
+
+            fn round(self) -> Self {
+                $id::round(self)
+            }
+
+            fn rem_euclid(self, rhs: Self) -> Self {
+                $id::rem_euclid(self, rhs)
+            }
+        }
+    )+};
+}
+
+impl_scalar!(f32, f64);
@@ -0,0 +1,222 @@
+use crate::boundary::{Boundary, Domain};
+use crate::particle_system::Particle;
+use crate::scalar::Scalar;
+use crate::vector::{SimVector, Vector2};
+
+/// Integer coordinates of a cell. Axes beyond the dimension of the vectors stay 0.
+type Cell = [i64; 3];
+
+/// Uniform grid over unbounded space. Cells are hashed into a table sized to the particle count,
+/// so memory stays linear in the number of particles regardless of how far they spread.
+/// Over a periodic domain the cells tile the domain exactly and neighbour lookups wrap around.
+/// Works in up to three dimensions.
+#[derive(Default)]
+pub struct SpatialGrid<V = Vector2<f64>> {
+    cell_size: V,
+    origin: V,
+    period: Option<Cell>,
+    cells: Vec<Cell>,
+    bucket_start: Vec<usize>,
+    entries: Vec<usize>,
+}
+
+impl<V: SimVector> SpatialGrid<V> {
+    pub fn rebuild(
+        &mut self,
+        positions: impl Iterator<Item = V>,
+        cell_size: V::Scalar,
+        periodic: Option<&Domain<V>>,
+    ) {
+        assert!(V::LEN <= 3, "the spatial grid has at most three dimensions");
+        match periodic {
+            Some(domain) => {
+                let counts = domain.size().map(|l| {
+                    let n = ((l / cell_size).floor().to_f64() as i64).max(1);
+                    V::Scalar::from_f64(n as f64)
+                });
+                self.cell_size = domain.size().zip_map(counts, |l, n| l / n);
+                self.origin = domain.min;
+                let mut period = [1; 3];
+                for (i, n) in period.iter_mut().enumerate().take(V::LEN) {
+                    *n = counts.get(i).to_f64() as i64;
+                }
+                self.period = Some(period);
+            }
+            None => {
+                self.cell_size = V::from_fn(|_| cell_size);
+                self.origin = V::default();
+                self.period = None;
+            }
+        }
+
+        self.cells.clear();
+        for p in positions {
+            let cell = self.cell_of(p);
+            self.cells.push(cell);
+        }
+
+        let table = self.cells.len().max(1).next_power_of_two();
+        self.bucket_start.clear();
+        self.bucket_start.resize(table + 1, 0);
+        for &cell in &self.cells {
+            self.bucket_start[Self::bucket(cell, table) + 1] += 1;
+        }
+        for i in 0..table {
+            self.bucket_start[i + 1] += self.bucket_start[i];
+        }
+
+        let mut cursor = self.bucket_start.clone();
+        self.entries.clear();
+        self.entries.resize(self.cells.len(), 0);
+        for (i, &cell) in self.cells.iter().enumerate() {
+            let b = Self::bucket(cell, table);
+            self.entries[cursor[b]] = i;
+            cursor[b] += 1;
+        }
+    }
+
+    /// Calls `f` with the index of every particle in the block of 3 cells per axis around
+    /// `position`. Each index is visited at most once; callers still have to filter by distance.
+    pub fn for_each_neighbor(&self, position: V, mut f: impl FnMut(usize)) {
+        if self.cells.is_empty() {
+            return;
+        }
+        let table = self.bucket_start.len() - 1;
+        let center = self.cell_of(position);
+        let axes: [_; 3] = std::array::from_fn(|i| {
+            if i < V::LEN {
+                Self::neighbor_cells(center[i], self.period.map(|p| p[i]))
+            } else {
+                ([0; 3], 1)
+            }
+        });
+        // x が最も速く変わる順に並べる．
+        for &z in &axes[2].0[..axes[2].1] {
+            for &y in &axes[1].0[..axes[1].1] {
+                for &x in &axes[0].0[..axes[0].1] {
+                    let cell = [x, y, z];
+                    let b = Self::bucket(cell, table);
+                    for &i in &self.entries[self.bucket_start[b]..self.bucket_start[b + 1]] {
+                        if self.cells[i] == cell {
+                            f(i);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn neighbor_cells(c: i64, period: Option<i64>) -> ([i64; 3], usize) {
+        let Some(n) = period else {
+            return ([c - 1, c, c + 1], 3);
+        };
+        // 周期が 3 未満だと隣のセルが重複するので取り除く．
+        let mut cells = [0; 3];
+        let mut len = 0;
+        for c in [c - 1, c, c + 1] {
+            let c = c.rem_euclid(n);
+            if !cells[..len].contains(&c) {
+                cells[len] = c;
+                len += 1;
+            }
+        }
+        (cells, len)
+    }
+
+    fn cell_of(&self, position: V) -> Cell {
+        let mut cell = [0; 3];
+        for (i, c) in cell.iter_mut().enumerate().take(V::LEN) {
+            let x = ((*position.get(i) - *self.origin.get(i)) / *self.cell_size.get(i))
+                .floor()
+                .to_f64() as i64;
+            *c = match self.period {
+                Some(period) => x.rem_euclid(period[i]),
+                None => x,
+            };
+        }
+        cell
+    }
+
+    fn bucket([x, y, z]: Cell, table: usize) -> usize {
+        let h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        (h >> 32) as usize & (table - 1)
+    }
+}
+
+/// Neighbour lookup over one set of particle positions, honouring the cutoff and boundary.
+/// Without a cutoff every other particle is a neighbour.
+pub struct Neighborhood<'a, V: SimVector = Vector2<f64>> {
+    cutoff: Option<V::Scalar>,
+    boundary: &'a Boundary<V>,
+    grid: &'a SpatialGrid<V>,
+}
+
+impl<'a, V: SimVector> Neighborhood<'a, V> {
+    pub fn new<Props>(
+        cutoff: Option<V::Scalar>,
+        boundary: &'a Boundary<V>,
+        grid: &'a mut SpatialGrid<V>,
+        particles: &[Particle<Props, V>],
+    ) -> Neighborhood<'a, V> {
+        if let Some(r) = cutoff {
+            let periodic = boundary.domain().filter(|_| boundary.is_periodic());
+            grid.rebuild(particles.iter().map(|p| p.position), r, periodic);
+        }
+        Neighborhood {
+            cutoff,
+            boundary,
+            grid,
+        }
+    }
+
+    /// Calls `f` once with `i < j` for every pair of particles within the cutoff.
+    pub fn for_each_pair<Props>(
+        &self,
+        particles: &[Particle<Props, V>],
+        mut f: impl FnMut(usize, usize),
+    ) {
+        for i in 0..particles.len() {
+            match self.cutoff {
+                Some(_) => self.for_each(particles, i, |j, _| {
+                    if i < j {
+                        f(i, j);
+                    }
+                }),
+                None => (i + 1..particles.len()).for_each(|j| f(i, j)),
+            }
+        }
+    }
+
+    /// Calls `f` with every particle other than `particles[i]` that lies within the cutoff.
+    pub fn for_each<Props>(
+        &self,
+        particles: &[Particle<Props, V>],
+        i: usize,
+        mut f: impl FnMut(usize, &Particle<Props, V>),
+    ) {
+        let p0 = &particles[i];
+        match self.cutoff {
+            Some(r) => self.grid.for_each_neighbor(p0.position, |j| {
+                let p1 = &particles[j];
+                if i != j
+                    && self
+                        .boundary
+                        .separation(p0.position, p1.position)
+                        .square_length()
+                        < r * r
+                {
+                    f(j, p1);
+                }
+            }),
+            None => {
+                for (j, p1) in particles.iter().enumerate() {
+                    if i != j {
+                        f(j, p1);
+                    }
+                }
+            }
+        }
+    }
+}
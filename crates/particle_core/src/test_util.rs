@@ -0,0 +1,58 @@
+//! Helpers shared by the unit tests of the other modules.
+
+use std::marker::PhantomData;
+
+use crate::particle_system::{Particle, ParticleSystemParameters};
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::vector::SimVector;
+
+/// Particles without external or pairwise forces, for tests that only exercise
+/// bonds, constraints, thermostats or emitters.
+pub struct Free<V>(pub PhantomData<V>);
+
+impl<V: SimVector> ParticleSystemParameters for Free<V> {
+    type Props = ();
+
+    type Vector = V;
+
+    fn external_force(&self, _: &Particle<(), V>, _: V::Scalar, _: &mut SimRng) -> V {
+        V::default()
+    }
+
+    fn cutoff_radius(&self) -> Option<V::Scalar> {
+        Some(V::Scalar::from_f64(1e-3))
+    }
+}
+
+/// Relative error allowed when comparing results computed in `S` with their
+/// closed form.
+pub fn tolerance<S: Scalar>() -> f64 {
+    4e3 * S::EPSILON.to_f64()
+}
+
+/// Runs each generic `fn name<S: Scalar>()` once in `f64` as `double::name`
+/// and once in `f32` as `single::name`.
+macro_rules! scalar_tests {
+    ($($name:ident),+ $(,)?) => {
+        mod double {
+            $(
+                #[test]
+                fn $name() {
+                    super::$name::<f64>();
+                }
+            )+
+        }
+
+        mod single {
+            $(
+                #[test]
+                fn $name() {
+                    super::$name::<f32>();
+                }
+            )+
+        }
+    };
+}
+
+pub(crate) use scalar_tests;
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostics::Diagnostics;
+use crate::particle_system::Particle;
+use crate::rng::SimRng;
+use crate::scalar::Scalar;
+use crate::vector::SimVector;
+
+/// Couples the particles to a heat bath at `temperature`, with Boltzmann's constant taken as 1 as
+/// in `Diagnostics`. Velocities relax towards the bath over `coupling_time`, whatever the time step.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Thermostat {
+    /// Friction and Gaussian kicks on every particle (Langevin dynamics). Samples the canonical
+    /// ensemble, but does not conserve momentum.
+    Langevin {
+        temperature: f64,
+        coupling_time: f64,
+    },
+    /// Rescales the velocities relative to the centre of mass so that the measured temperature
+    /// approaches `temperature` exponentially. Deterministic and conserves momentum, but fluctuations
+    /// of the temperature are suppressed.
+    Berendsen {
+        temperature: f64,
+        coupling_time: f64,
+    },
+}
+
+impl Thermostat {
+    pub fn temperature(&self) -> f64 {
+        match *self {
+            Thermostat::Langevin { temperature, .. }
+            | Thermostat::Berendsen { temperature, .. } => temperature,
+        }
+    }
+
+    /// Adjusts the velocities of `particles` after a step of `delta_time`.
+    pub fn apply<Props, V: SimVector>(
+        &self,
+        particles: &mut [Particle<Props, V>],
+        delta_time: V::Scalar,
+        rng: &mut SimRng,
+    ) {
+        let delta_time = delta_time.to_f64();
+        match *self {
+            Thermostat::Langevin {
+                temperature,
+                coupling_time,
+            } => {
+                // 摩擦と揺らぎの Ornstein–Uhlenbeck 過程を厳密に解くので，刻み幅によらない．
+                let c = (-delta_time / coupling_time).exp();
+                for p in particles {
+                    let sigma = ((1.0 - c * c) * temperature / p.mass.to_f64()).sqrt();
+                    let kick = V::from_fn(|_| V::Scalar::from_f64(sigma * rng.gaussian()));
+                    p.velocity = p.velocity * V::Scalar::from_f64(c) + kick;
+                }
+            }
+            Thermostat::Berendsen {
+                temperature,
+                coupling_time,
+            } => {
+                let all: Vec<_> = particles.iter().collect();
+                let measured = Diagnostics::measure(&all, None);
+                if measured.temperature <= 0.0 {
+                    return;
+                }
+                let ratio = temperature / measured.temperature - 1.0;
+                let lambda = (1.0 + delta_time / coupling_time * ratio).max(0.0).sqrt();
+                let center_velocity = measured.momentum / V::Scalar::from_f64(measured.mass);
+                for p in particles {
+                    let relative = p.velocity - center_velocity;
+                    p.velocity = center_velocity + relative * V::Scalar::from_f64(lambda);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use super::*;
+    use crate::particle_system::ParticleSystem;
+    use crate::test_util::{scalar_tests, tolerance, Free};
+    use crate::vector::Vector2;
+
+    /// Ideal gas.
+    fn gas<S: Scalar>(count: usize, speed: f64) -> ParticleSystem<Free<Vector2<S>>> {
+        let mut rng = SimRng::new(1);
+        let particles = (0..count).map(|i| Particle {
+            props: (),
+            mass: S::from_f64(1.0 + (i % 3) as f64),
+            position: Vector2 {
+                x: S::from_f64(i as f64),
+                y: S::ZERO,
+            },
+            velocity: Vector2 {
+                x: S::from_f64(speed * rng.gaussian()),
+                y: S::from_f64(speed * rng.gaussian()),
+            },
+            radius: None,
+            age: S::ZERO,
+            lifetime: None,
+        });
+        ParticleSystem::new(Free(PhantomData), particles)
+    }
+
+    fn test_langevin_reaches_temperature_at_any_step<S: Scalar>() {
+        for delta_time in [0.01, 0.2] {
+            let thermostat = Thermostat::Langevin {
+                temperature: 2.0,
+                coupling_time: 0.5,
+            };
+            let mut system = gas::<S>(2000, 0.0).with_thermostat(thermostat);
+            let steps = (5.0 / delta_time) as usize;
+            for _ in 0..steps {
+                system.update(S::from_f64(delta_time));
+            }
+
+            let temperature = system.diagnostics().temperature;
+            assert!((temperature - 2.0).abs() < 0.1, "{temperature}");
+        }
+    }
+
+    fn test_berendsen_relaxes_exponentially<S: Scalar>() {
+        let mut system = gas::<S>(500, 3.0).with_thermostat(Thermostat::Berendsen {
+            temperature: 1.0,
+            coupling_time: 0.5,
+        });
+        let before = system.diagnostics();
+        for _ in 0..100 {
+            system.update(S::from_f64(0.01));
+        }
+        let after = system.diagnostics();
+
+        // 1 刻みごとに差が (1 - dt/τ) 倍になる．
+        let tolerance = 1e3 * tolerance::<S>();
+        let expected = 1.0 + (before.temperature - 1.0) * (1.0 - 0.01 / 0.5f64).powi(100);
+        assert!((after.temperature - expected).abs() < tolerance);
+        assert!((after.momentum - before.momentum).length().to_f64() < tolerance);
+    }
+
+    scalar_tests!(
+        test_langevin_reaches_temperature_at_any_step,
+        test_berendsen_relaxes_exponentially,
+    );
+}
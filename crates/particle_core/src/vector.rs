@@ -0,0 +1,122 @@
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use fixed_vector::{fixed_vector, Sqrt, Vector, VectorDot};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::particle_system::MaybeSync;
+use crate::scalar::Scalar;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[fixed_vector(T; x, y)]
+pub struct Vector2<T> {
+    pub x: T,
+    pub y: T,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[fixed_vector(T; x, y, z)]
+pub struct Vector3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+macro_rules! impl_length {
+    ($($id:ident),+) => {$(
+        impl<T: Mul + Copy> $id<T>
+        where
+            T::Output: std::iter::Sum,
+        {
+            pub fn square_length(self) -> T::Output {
+                self.dot(self)
+            }
+        }
+
+        impl<T: Mul + Copy> $id<T>
+        where
+            <T as Mul>::Output: std::iter::Sum + Sqrt,
+        {
+            pub fn length(self) -> <<T as Mul>::Output as Sqrt>::Output {
+                self.square_length().sqrt()
+            }
+        }
+    )+};
+}
+
+impl_length!(Vector2, Vector3);
+
+/// Positions, velocities and forces of a simulation: `Vector2` in the plane and `Vector3` in
+/// space, over any `Scalar`.
+pub trait SimVector:
+    Vector<Self::Scalar>
+    + VectorDot<Self::Scalar>
+    + Copy
+    + Default
+    + Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Neg<Output = Self>
+    + Mul<Self::Scalar, Output = Self>
+    + Div<Self::Scalar, Output = Self>
+    + AddAssign
+    + SubAssign
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + MaybeSync
+{
+    type Scalar: Scalar;
+    /// Cross products in `f64`: the component along the normal of the plane for `Vector2`, a
+    /// `Vector3<f64>` in space.
+    type Cross: Copy + Default + Debug + AddAssign + Mul<f64, Output = Self::Cross>;
+
+    fn cross(self, other: Self) -> Self::Cross;
+
+    fn square_length(self) -> Self::Scalar {
+        self.dot(self)
+    }
+
+    fn length(self) -> Self::Scalar {
+        self.square_length().sqrt()
+    }
+
+    /// Applies `f` to every component.
+    fn map(self, mut f: impl FnMut(Self::Scalar) -> Self::Scalar) -> Self {
+        Self::from_fn(|i| f(*self.get(i)))
+    }
+
+    /// Applies `f` to the matching components of `self` and `other`.
+    fn zip_map(
+        self,
+        other: Self,
+        mut f: impl FnMut(Self::Scalar, Self::Scalar) -> Self::Scalar,
+    ) -> Self {
+        Self::from_fn(|i| f(*self.get(i), *other.get(i)))
+    }
+}
+
+impl<S: Scalar> SimVector for Vector2<S> {
+    type Scalar = S;
+    type Cross = f64;
+
+    fn cross(self, other: Self) -> f64 {
+        let [ax, ay, bx, by] = [self.x, self.y, other.x, other.y].map(S::to_f64);
+        ax * by - ay * bx
+    }
+}
+
+impl<S: Scalar> SimVector for Vector3<S> {
+    type Scalar = S;
+    type Cross = Vector3<f64>;
+
+    fn cross(self, other: Self) -> Vector3<f64> {
+        let [ax, ay, az] = [self.x, self.y, self.z].map(S::to_f64);
+        let [bx, by, bz] = [other.x, other.y, other.z].map(S::to_f64);
+        Vector3 {
+            x: ay * bz - az * by,
+            y: az * bx - ax * bz,
+            z: ax * by - ay * bx,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+usage: particle_runner [options]
+
+Runs the simulation without a browser and writes the particles of every `--every`th step,
+starting with the initial state.
+
+options:
+  --scenario <path>   start from a snapshot (.json or .bin) instead of the random scene
+  --seed <n>          seed of the random scene [default: 0]
+  --steps <n>         number of steps to run [default: 600]
+  --dt <seconds>      length of one step [default: 1/60]
+  --every <n>         write every nth step [default: 1]
+  --format <format>   csv or ndjson [default: csv]
+  --output <path>     file to write frames to [default: stdout]
+  --help              print this message";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Ndjson,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub scenario: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub steps: u64,
+    pub dt: f64,
+    pub every: u64,
+    pub format: Format,
+    pub output: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            scenario: None,
+            seed: None,
+            steps: 600,
+            dt: 1.0 / 60.0,
+            every: 1,
+            format: Format::Csv,
+            output: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Options),
+    Help,
+}
+
+/// Parses the arguments following the program name. Errors are messages meant for the user.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg, None),
+        };
+        if name == "--help" || name == "-h" {
+            return Ok(Command::Help);
+        }
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("`{name}` needs a value"))
+        };
+        match name.as_str() {
+            "--scenario" => options.scenario = Some(PathBuf::from(value()?)),
+            "--seed" => options.seed = Some(number(&name, &value()?, "a non-negative integer")?),
+            "--steps" => options.steps = number(&name, &value()?, "a non-negative integer")?,
+            "--dt" => {
+                options.dt = number(&name, &value()?, "a positive number")?;
+                if !(options.dt.is_finite() && options.dt > 0.0) {
+                    return Err(format!(
+                        "`--dt` must be a positive number, got {}",
+                        options.dt
+                    ));
+                }
+            }
+            "--every" => {
+                options.every = number(&name, &value()?, "a positive integer")?;
+                if options.every == 0 {
+                    return Err("`--every` must be at least 1".to_owned());
+                }
+            }
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "csv" => Format::Csv,
+                    "ndjson" => Format::Ndjson,
+                    other => {
+                        return Err(format!("unknown format `{other}`, expected csv or ndjson"))
+                    }
+                }
+            }
+            "--output" => options.output = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unknown option `{name}`")),
+        }
+    }
+    if options.scenario.is_some() && options.seed.is_some() {
+        return Err(
+            "`--seed` cannot be combined with `--scenario`, the snapshot has its own".to_owned(),
+        );
+    }
+    Ok(Command::Run(options))
+}
+
+fn number<T: FromStr>(name: &str, value: &str, expected: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for `{name}`, expected {expected}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<Command, String> {
+        parse(args.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn test_parses_options() {
+        let command =
+            parse_str("--seed 3 --steps=10 --dt 0.5 --every 2 --format ndjson --output out.ndjson");
+
+        assert_eq!(
+            command,
+            Ok(Command::Run(Options {
+                seed: Some(3),
+                steps: 10,
+                dt: 0.5,
+                every: 2,
+                format: Format::Ndjson,
+                output: Some(PathBuf::from("out.ndjson")),
+                ..Options::default()
+            }))
+        );
+        assert_eq!(parse_str(""), Ok(Command::Run(Options::default())));
+        assert_eq!(parse_str("--steps 1 --help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let error = |args| parse_str(args).unwrap_err();
+
+        assert_eq!(error("--steps"), "`--steps` needs a value");
+        assert_eq!(
+            error("--steps ten"),
+            "invalid value `ten` for `--steps`, expected a non-negative integer"
+        );
+        assert_eq!(error("--dt -1"), "`--dt` must be a positive number, got -1");
+        assert_eq!(
+            error("--dt inf"),
+            "`--dt` must be a positive number, got inf"
+        );
+        assert_eq!(error("--every 0"), "`--every` must be at least 1");
+        assert_eq!(
+            error("--format xml"),
+            "unknown format `xml`, expected csv or ndjson"
+        );
+        assert_eq!(error("--fast"), "unknown option `--fast`");
+        assert!(error("--scenario a.json --seed 1").contains("cannot be combined"));
+    }
+}
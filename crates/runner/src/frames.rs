@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+
+use particle_core::particle_param::ParticleParam;
+use particle_core::particle_system::ParticleSystem;
+use particle_core::vector::Vector2;
+use serde::Serialize;
+
+use crate::args::Format;
+
+/// Writes the state of every particle, one row per particle and frame.
+pub struct FrameWriter<W: Write> {
+    out: W,
+    format: Format,
+}
+
+#[derive(Serialize)]
+struct Row {
+    step: u64,
+    id: String,
+    kind: usize,
+    position: Vector2<f64>,
+    velocity: Vector2<f64>,
+}
+
+impl<W: Write> FrameWriter<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<FrameWriter<W>> {
+        if format == Format::Csv {
+            writeln!(out, "step,id,kind,x,y,vx,vy")?;
+        }
+        Ok(FrameWriter { out, format })
+    }
+
+    pub fn write_frame(
+        &mut self,
+        step: u64,
+        system: &ParticleSystem<ParticleParam>,
+    ) -> io::Result<()> {
+        for (id, p) in system.iter() {
+            match self.format {
+                Format::Csv => writeln!(
+                    self.out,
+                    "{},{},{},{},{},{},{}",
+                    step, id, p.props, p.position.x, p.position.y, p.velocity.x, p.velocity.y
+                )?,
+                Format::Ndjson => {
+                    let row = Row {
+                        step,
+                        id: id.to_string(),
+                        kind: p.props,
+                        position: p.position,
+                        velocity: p.velocity,
+                    };
+                    serde_json::to_writer(&mut self.out, &row)?;
+                    writeln!(self.out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use particle_core::particle_param::random_scenario;
+
+    use super::*;
+
+    fn write(format: Format) -> String {
+        let mut system = random_scenario(2, 3, 100.0, 0);
+        let mut writer = FrameWriter::new(Vec::new(), format).unwrap();
+        writer.write_frame(0, &system).unwrap();
+        system.update(0.1);
+        writer.write_frame(1, &system).unwrap();
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_csv_has_header_and_row_per_particle() {
+        let csv = write(Format::Csv);
+        let lines: Vec<_> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 1 + 2 * 6);
+        assert_eq!(lines[0], "step,id,kind,x,y,vx,vy");
+        assert!(lines[1].starts_with("0,0v0,0,"));
+        assert!(lines[12].starts_with("1,5v0,1,"));
+        assert!(lines[1..].iter().all(|l| l.split(',').count() == 7));
+    }
+
+    #[test]
+    fn test_ndjson_lines_are_objects() {
+        let ndjson = write(Format::Ndjson);
+        let rows: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(rows.len(), 2 * 6);
+        assert_eq!(rows[7]["step"], 1);
+        assert_eq!(rows[7]["id"], "1v0");
+        assert_eq!(rows[7]["kind"], 0);
+        assert!(rows[7]["velocity"]["x"].is_f64());
+    }
+}
@@ -0,0 +1,93 @@
+mod args;
+mod frames;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use particle_core::particle_param::{random_scenario, ParticleParam};
+use particle_core::particle_system::ParticleSystem;
+
+use args::{Command, Options};
+use frames::FrameWriter;
+
+// キャンバスと同じ場面．
+static KINDS: usize = 6;
+static PER_KIND: usize = 30;
+static SIZE: f64 = 500.0;
+
+fn main() -> ExitCode {
+    let options = match args::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", args::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {message}\n\n{}", args::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let mut system = match &options.scenario {
+        Some(path) => load(path)?,
+        None => random_scenario(KINDS, PER_KIND, SIZE, options.seed.unwrap_or(0)),
+    };
+    let out: Box<dyn Write> = match &options.output {
+        Some(path) => Box::new(
+            File::create(path).map_err(|e| format!("cannot create `{}`: {e}", path.display()))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    match write_frames(&mut system, options, BufWriter::new(out)) {
+        // 読み手が先に終了した（`head` など）なら，それ以上書く必要はない．
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| format!("cannot write frames: {e}")),
+    }
+}
+
+fn write_frames(
+    system: &mut ParticleSystem<ParticleParam>,
+    options: &Options,
+    out: impl Write,
+) -> io::Result<()> {
+    let mut writer = FrameWriter::new(out, options.format)?;
+    writer.write_frame(0, system)?;
+    for step in 1..=options.steps {
+        system.update(options.dt);
+        if step % options.every == 0 {
+            writer.write_frame(step, system)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads a snapshot written by `ParticleSystem::to_json` or `to_bytes`, told apart by extension.
+fn load(path: &Path) -> Result<ParticleSystem<ParticleParam>, String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("invalid snapshot `{}`: {e}", path.display());
+    let extension = path.extension().and_then(|e| e.to_str());
+    if !matches!(extension, Some("json" | "bin")) {
+        return Err(format!(
+            "cannot tell the format of `{}`, expected a .json or .bin snapshot",
+            path.display()
+        ));
+    }
+    let bytes = fs::read(path).map_err(|e| format!("cannot read `{}`: {e}", path.display()))?;
+    if extension == Some("json") {
+        let json = std::str::from_utf8(&bytes).map_err(|e| invalid(&e))?;
+        ParticleSystem::from_json(json).map_err(|e| invalid(&e))
+    } else {
+        ParticleSystem::from_bytes(&bytes).map_err(|e| invalid(&e))
+    }
+}